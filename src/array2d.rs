@@ -1,11 +1,52 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    fmt::Display,
+    str::FromStr,
+};
+
+use anyhow::Context;
 use itertools::Itertools;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedArray2D<T>"))]
 pub struct Array2D<T> {
     pub data: Vec<T>,
     pub width: usize,
     pub height: usize,
 }
 
+// Deserialized as-is and then checked, so the size always matches the data
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedArray2D<T> {
+    data: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<UncheckedArray2D<T>> for Array2D<T> {
+    type Error = anyhow::Error;
+
+    fn try_from(array: UncheckedArray2D<T>) -> Result<Self, Self::Error> {
+        anyhow::ensure!(
+            array.width.checked_mul(array.height) == Some(array.data.len()),
+            "{}x{} array can't hold {} cells",
+            array.width,
+            array.height,
+            array.data.len()
+        );
+
+        Ok(Array2D {
+            data: array.data,
+            width: array.width,
+            height: array.height,
+        })
+    }
+}
+
 impl<T> Array2D<T>
 where
     T: Copy + 'static,
@@ -48,14 +89,14 @@ where
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = T> + 'a {
-        self.data.iter().map(|x| *x)
+        self.data.iter().copied()
     }
 
     pub fn i_to_coords(&self, i: usize) -> (i32, i32) {
         ((i % self.width) as i32, (i / self.width) as i32)
     }
 
-    pub fn adjacent_mut<'a, 'b>(&'a mut self, i: usize, adjecent: &'b mut Vec<(usize, &'a mut T)>) {
+    pub fn adjacent_mut<'a>(&'a mut self, i: usize, adjecent: &mut Vec<(usize, &'a mut T)>) {
         let (center_x, center_y) = self.i_to_coords(i);
         let width = self.width;
         // this is not efficient, but the borrow checker wouldn't have it any other way
@@ -82,7 +123,7 @@ where
             self.try_get_with_i(x, y + 1),
         ]
         .into_iter()
-        .filter_map(|x| x)
+        .flatten()
         {
             adjacent.push(neighbor);
         }
@@ -105,6 +146,81 @@ where
         let data = s.into_iter().flatten().collect();
         Array2D::from_data_and_rows(data, rows)
     }
+
+    /// Number of cardinal steps from each cell to the nearest source, moving only through
+    /// passable cells. Unreachable cells are `None`.
+    pub fn bfs_distance_field(
+        &self,
+        sources: impl IntoIterator<Item = usize>,
+        passable: impl Fn(T) -> bool,
+    ) -> Array2D<Option<u32>> {
+        let mut field = self.map(|_| None);
+        let mut queue = VecDeque::new();
+
+        for source in sources {
+            if field.data[source].is_none() {
+                field.data[source] = Some(0);
+                queue.push_back(source);
+            }
+        }
+
+        let mut adjacent = Vec::new();
+
+        while let Some(i) = queue.pop_front() {
+            let distance = field.data[i].unwrap();
+            self.adjacent_cardinal(i, &mut adjacent);
+
+            for (j, value) in adjacent.drain(..) {
+                if field.data[j].is_none() && passable(value) {
+                    field.data[j] = Some(distance + 1);
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        field
+    }
+
+    /// Lowest total cost from the nearest source to each cell, where `cost` gives the price of
+    /// entering a cell, or `None` if it can't be entered. Unreachable cells are `None`, and
+    /// distances too large for a u32 saturate at `u32::MAX`.
+    pub fn dijkstra_distance_field(
+        &self,
+        sources: impl IntoIterator<Item = usize>,
+        cost: impl Fn(T) -> Option<u32>,
+    ) -> Array2D<Option<u32>> {
+        let mut field = self.map(|_| None);
+        let mut heap = BinaryHeap::new();
+
+        for source in sources {
+            field.data[source] = Some(0);
+            heap.push(Reverse((0u32, source)));
+        }
+
+        let mut adjacent = Vec::new();
+
+        while let Some(Reverse((distance, i))) = heap.pop() {
+            if field.data[i].is_some_and(|best| distance > best) {
+                continue;
+            }
+
+            self.adjacent_cardinal(i, &mut adjacent);
+
+            for (j, value) in adjacent.drain(..) {
+                let next = match cost(value) {
+                    Some(cost) => distance.saturating_add(cost),
+                    None => continue,
+                };
+
+                if field.data[j].is_none_or(|best| next < best) {
+                    field.data[j] = Some(next);
+                    heap.push(Reverse((next, j)));
+                }
+            }
+        }
+
+        field
+    }
 }
 
 fn i_to_coords(i: usize, width: usize) -> (i32, i32) {
@@ -124,6 +240,74 @@ impl Array2D<u8> {
     }
 }
 
+// A "<width>x<height>" line, then cells separated by spaces and rows by newlines, which FromStr
+// reads back. Cells that print empty or with whitespace in them don't survive the trip, but the
+// size line turns that into an error instead of a differently shaped array.
+impl<T: Display> Display for Array2D<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}x{}", self.width, self.height)?;
+
+        for y in 0..self.height {
+            let row = &self.data[y * self.width..(y + 1) * self.width];
+            writeln!(f, "{}", row.iter().join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> FromStr for Array2D<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+
+        let header = lines.next().context("missing size line")?;
+        let (width, height) = header
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .with_context(|| format!("invalid size line {:?}", header))?;
+
+        let mut data = Vec::new();
+        let mut rows = 0;
+
+        for (y, line) in lines.enumerate() {
+            anyhow::ensure!(y < height, "more than {} rows", height);
+            let row_start = data.len();
+
+            for (x, cell) in line.split_whitespace().enumerate() {
+                let value = cell
+                    .parse()
+                    .with_context(|| format!("invalid cell {:?} at ({}, {})", cell, x, y))?;
+                data.push(value);
+            }
+
+            let row_width = data.len() - row_start;
+            anyhow::ensure!(
+                row_width == width,
+                "row {} has {} cells, expected {}",
+                y,
+                row_width,
+                width
+            );
+
+            rows += 1;
+        }
+
+        anyhow::ensure!(rows == height, "expected {} rows, got {}", height, rows);
+
+        Ok(Array2D {
+            data,
+            width,
+            height,
+        })
+    }
+}
+
 pub fn print_array2d(a: &Array2D<u8>) {
     for y in 0..a.height {
         for x in 0..a.width {
@@ -132,3 +316,111 @@ pub fn print_array2d(a: &Array2D<u8>) {
         println!();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bfs_field_goes_around_walls() {
+        let grid = Array2D::from_string("000\n110\n000\n");
+        let field = grid.bfs_distance_field([0], |x| x == 0);
+        let expected: Array2D<Option<u32>> = Array2D::from_vec_vec(vec![
+            vec![Some(0), Some(1), Some(2)],
+            vec![None, None, Some(3)],
+            vec![Some(6), Some(5), Some(4)],
+        ]);
+        assert_eq!(field, expected);
+    }
+
+    #[test]
+    fn bfs_field_with_multiple_sources() {
+        let grid = Array2D::from_string("00000\n");
+        let field = grid.bfs_distance_field([0, 4], |_| true);
+        assert_eq!(
+            field.data,
+            vec![Some(0), Some(1), Some(2), Some(1), Some(0)]
+        );
+    }
+
+    #[test]
+    fn dijkstra_field_day15_example() {
+        let map = Array2D::from_string(
+            "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n\
+             1319128137\n1359912421\n3125421639\n1293138521\n2311944581\n",
+        );
+        let field = map.dijkstra_distance_field([0], |risk| Some(risk as u32));
+        assert_eq!(field.data[map.data.len() - 1], Some(40));
+        assert_eq!(field.data[0], Some(0));
+    }
+
+    #[test]
+    fn dijkstra_field_saturates() {
+        let map = Array2D::from_vec_vec(vec![vec![0u8, 1, 1, 1]]);
+        let field = map.dijkstra_distance_field([0], |_| Some(u32::MAX / 2));
+        assert_eq!(
+            field.data,
+            vec![
+                Some(0),
+                Some(u32::MAX / 2),
+                Some(u32::MAX - 1),
+                Some(u32::MAX)
+            ]
+        );
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let costs = Array2D::from_vec_vec(vec![vec![1u32, 163, 7], vec![12, 0, 9999]]);
+        let text = costs.to_string();
+        assert_eq!(text, "3x2\n1 163 7\n12 0 9999\n");
+        assert_eq!(text.parse::<Array2D<u32>>().unwrap(), costs);
+    }
+
+    #[test]
+    fn ragged_rows_are_rejected() {
+        assert!("3x2\n1 2 3\n4 5\n".parse::<Array2D<u8>>().is_err());
+        assert!("2x2\n1 2\n3 x\n".parse::<Array2D<u8>>().is_err());
+        assert!("2x3\n1 2\n3 4\n".parse::<Array2D<u8>>().is_err());
+        assert!("2x1\n1 2\n3 4\n".parse::<Array2D<u8>>().is_err());
+        assert!("1 2\n3 4\n".parse::<Array2D<u8>>().is_err());
+    }
+
+    #[test]
+    fn non_copy_cells() {
+        let grid = "2x2\nab c\nd efg\n".parse::<Array2D<String>>().unwrap();
+        assert_eq!((grid.width, grid.height), (2, 2));
+        assert_eq!(grid.data[3], "efg");
+        assert_eq!(grid.to_string().parse::<Array2D<String>>().unwrap(), grid);
+
+        // These would come back in a different shape, so they don't come back at all
+        for cells in [vec!["a b", "c"], vec!["", "c"]] {
+            let grid = Array2D::from_vec_vec(vec![cells]).map(String::from);
+            assert!(grid.to_string().parse::<Array2D<String>>().is_err());
+        }
+    }
+
+    #[test]
+    fn zero_width_round_trip() {
+        let grid: Array2D<u8> = Array2D {
+            data: Vec::new(),
+            width: 0,
+            height: 3,
+        };
+        let text = grid.to_string();
+        assert_eq!(text, "0x3\n\n\n\n");
+        assert_eq!(text.parse::<Array2D<u8>>().unwrap(), grid);
+        assert_eq!("0x0\n".parse::<Array2D<u8>>().unwrap().height, 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let grid = Array2D::from_string("123\n456\n");
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(serde_json::from_str::<Array2D<u8>>(&json).unwrap(), grid);
+
+        let wrong_size = r#"{"data":[1,2,3],"width":2,"height":2}"#;
+        assert!(serde_json::from_str::<Array2D<u8>>(wrong_size).is_err());
+    }
+}
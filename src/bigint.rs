@@ -0,0 +1,198 @@
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, Mul},
+};
+
+/// An arbitrary precision unsigned integer, stored as little endian base 2^32 limbs
+/// without trailing zero limbs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 32 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn normalize(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    /// Divides in place by a small divisor and returns the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+
+        for limb in self.limbs.iter_mut().rev() {
+            let current = (remainder << 32) | *limb as u64;
+            *limb = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+
+        *self = std::mem::take(self).normalize();
+        remainder as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        BigUint {
+            limbs: vec![n as u32, (n >> 32) as u32],
+        }
+        .normalize()
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(rhs.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+
+        for i in 0..len {
+            let a = self.limbs.get(i).copied().unwrap_or(0) as u64;
+            let b = rhs.limbs.get(i).copied().unwrap_or(0) as u64;
+            let sum = a + b + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+
+        limbs.push(carry as u32);
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: BigUint) -> BigUint {
+        &self + &rhs
+    }
+}
+
+// Schoolbook multiplication, which is plenty for a few thousand bits
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: &BigUint) -> BigUint {
+        if self.is_zero() || rhs.is_zero() {
+            return BigUint::zero();
+        }
+
+        let mut limbs = vec![0u32; self.limbs.len() + rhs.limbs.len()];
+
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+
+            for (j, &b) in rhs.limbs.iter().enumerate() {
+                let current = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                limbs[i + j] = current as u32;
+                carry = current >> 32;
+            }
+
+            limbs[i + rhs.limbs.len()] = carry as u32;
+        }
+
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: BigUint) -> BigUint {
+        &self * &rhs
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Peel off 9 decimal digits at a time
+        let mut n = self.clone();
+        let mut chunks = Vec::new();
+
+        while !n.is_zero() {
+            chunks.push(n.div_rem_small(1_000_000_000));
+        }
+
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from(1_000_000_000).to_string(), "1000000000");
+        assert_eq!(BigUint::from(u64::MAX).to_string(), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn arithmetic_past_u128() {
+        let two_64 = &BigUint::from(u64::MAX) + &BigUint::from(1);
+        assert_eq!(two_64.bits(), 65);
+
+        let two_128 = &two_64 * &two_64;
+        assert_eq!(
+            two_128.to_string(),
+            "340282366920938463463374607431768211456"
+        );
+
+        let ten_20 = &BigUint::from(10_000_000_000) * &BigUint::from(10_000_000_000);
+        assert_eq!(
+            (&ten_20 * &ten_20).to_string(),
+            format!("1{}", "0".repeat(40))
+        );
+        assert!(two_128 > ten_20);
+    }
+
+    #[test]
+    fn multiplying_by_zero() {
+        assert!((&BigUint::from(12345) * &BigUint::zero()).is_zero());
+        assert_eq!(BigUint::from(0), BigUint::zero());
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    fmt::Display,
+    ops::{BitAndAssign, BitOrAssign},
+};
+
+use crate::array2d::Array2D;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+fn words_for(bits: usize) -> usize {
+    bits.div_ceil(WORD_BITS)
+}
+
+/// A grid of booleans with each row packed into `u64` words, least significant bit first.
+/// Bits past `width` are always zero.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    words: Vec<u64>,
+    words_per_row: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = words_for(width);
+        BitGrid {
+            words: vec![0; words_per_row * height],
+            words_per_row,
+            width,
+            height,
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let points: Vec<_> = points.into_iter().collect();
+        let width = points.iter().map(|(x, _)| x + 1).max().unwrap_or(0);
+        let height = points.iter().map(|(_, y)| y + 1).max().unwrap_or(0);
+
+        let mut grid = BitGrid::new(width, height);
+        for (x, y) in points {
+            grid.set(x, y, true);
+        }
+        grid
+    }
+
+    pub fn from_array2d(array: &Array2D<bool>) -> Self {
+        let mut grid = BitGrid::new(array.width, array.height);
+        for (i, value) in array.iter().enumerate() {
+            grid.set(i % array.width, i / array.width, value);
+        }
+        grid
+    }
+
+    pub fn to_array2d(&self) -> Array2D<bool> {
+        let data = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| self.get(x, y)))
+            .collect();
+
+        Array2D {
+            data,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[u64] {
+        &self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u64] {
+        &mut self.words[y * self.words_per_row..(y + 1) * self.words_per_row]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width, "x {} out of bounds", x);
+        (self.row(y)[x / WORD_BITS] >> (x % WORD_BITS)) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        assert!(x < self.width, "x {} out of bounds", x);
+        let word = &mut self.row_mut(y)[x / WORD_BITS];
+        let mask = 1 << (x % WORD_BITS);
+
+        if value {
+            *word |= mask;
+        } else {
+            *word &= !mask;
+        }
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn row_count_ones(&self, y: usize) -> usize {
+        self.row(y).iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn column_count_ones(&self, x: usize) -> usize {
+        (0..self.height).filter(|&y| self.get(x, y)).count()
+    }
+
+    /// Row `dst` becomes `dst | src`.
+    pub fn or_row(&mut self, dst: usize, src: usize) {
+        for i in 0..self.words_per_row {
+            self.words[dst * self.words_per_row + i] |= self.words[src * self.words_per_row + i];
+        }
+    }
+
+    /// Row `dst` becomes `dst & src`.
+    pub fn and_row(&mut self, dst: usize, src: usize) {
+        for i in 0..self.words_per_row {
+            self.words[dst * self.words_per_row + i] &= self.words[src * self.words_per_row + i];
+        }
+    }
+
+    /// Column `dst` becomes `dst | src`.
+    pub fn or_column(&mut self, dst: usize, src: usize) {
+        for y in 0..self.height {
+            let value = self.get(dst, y) | self.get(src, y);
+            self.set(dst, y, value);
+        }
+    }
+
+    /// Column `dst` becomes `dst & src`.
+    pub fn and_column(&mut self, dst: usize, src: usize) {
+        for y in 0..self.height {
+            let value = self.get(dst, y) & self.get(src, y);
+            self.set(dst, y, value);
+        }
+    }
+
+    /// Mirrors the grid top to bottom.
+    pub fn flip_vertical(&mut self) {
+        for y in 0..self.height / 2 {
+            for i in 0..self.words_per_row {
+                self.words.swap(
+                    y * self.words_per_row + i,
+                    (self.height - 1 - y) * self.words_per_row + i,
+                );
+            }
+        }
+    }
+
+    /// Mirrors the grid left to right.
+    pub fn flip_horizontal(&mut self) {
+        for y in 0..self.height {
+            let reversed = reverse_row(self.row(y), self.width);
+            self.row_mut(y).copy_from_slice(&reversed);
+        }
+    }
+
+    /// Folds the bottom half up over the line `y`, like a day 13 "fold along y=...".
+    /// The fold line itself is dropped, and folding below the last row pads with empty rows.
+    /// If the bottom half is the taller one, the result is as tall as it is and the top half
+    /// lands against the fold at the bottom.
+    pub fn fold_up(&mut self, y: usize) {
+        let height = y.max(self.height.saturating_sub(y + 1));
+        let mut words = vec![0; height * self.words_per_row];
+
+        for src in (0..self.height).filter(|&src| src != y) {
+            // Rows keep their distance from the fold, which sits just below the last row
+            let dst = height - src.abs_diff(y);
+            for i in 0..self.words_per_row {
+                words[dst * self.words_per_row + i] |= self.words[src * self.words_per_row + i];
+            }
+        }
+
+        self.words = words;
+        self.height = height;
+    }
+
+    /// Folds the right half left over the line `x`, like a day 13 "fold along x=...".
+    /// The fold line itself is dropped, and folding right of the last column pads with empty
+    /// columns. If the right half is the wider one, the result is as wide as it is and the left
+    /// half lands against the fold on the right.
+    pub fn fold_left(&mut self, x: usize) {
+        let width = x.max(self.width.saturating_sub(x + 1));
+        let words_per_row = words_for(width);
+        // Wide enough for both halves before they're cut down to the new width
+        let scratch = words_per_row.max(self.words_per_row);
+        let mut words = Vec::with_capacity(words_per_row * self.height);
+
+        for y in 0..self.height {
+            let mut near = self.row(y).to_vec();
+            near.resize(scratch, 0);
+            mask_row(&mut near, x);
+
+            let mut reversed = reverse_row(self.row(y), self.width);
+            reversed.resize(scratch, 0);
+
+            // Column i < x goes to width - x + i. Bit j of the reversed row is column
+            // width - 1 - j of this one, which goes to width + x - (self.width - 1 - j).
+            let mut folded = shift_left(&near, width - x);
+            let reflected = shift_left(&reversed, width + x + 1 - self.width);
+
+            for (word, reflected) in folded.iter_mut().zip(reflected) {
+                *word |= reflected;
+            }
+
+            folded.truncate(words_per_row);
+            mask_row(&mut folded, width);
+            words.extend(folded);
+        }
+
+        self.words = words;
+        self.words_per_row = words_per_row;
+        self.width = width;
+    }
+}
+
+/// Reverses the first `width` bits of a row.
+fn reverse_row(row: &[u64], width: usize) -> Vec<u64> {
+    let reversed: Vec<u64> = row.iter().rev().map(|w| w.reverse_bits()).collect();
+    shift_right(&reversed, row.len() * WORD_BITS - width)
+}
+
+fn shift_right(row: &[u64], bits: usize) -> Vec<u64> {
+    let (words, bits) = (bits / WORD_BITS, bits % WORD_BITS);
+
+    (0..row.len())
+        .map(|i| {
+            let lo = row.get(i + words).copied().unwrap_or(0);
+            let hi = row.get(i + words + 1).copied().unwrap_or(0);
+
+            if bits == 0 {
+                lo
+            } else {
+                (lo >> bits) | (hi << (WORD_BITS - bits))
+            }
+        })
+        .collect()
+}
+
+fn shift_left(row: &[u64], bits: usize) -> Vec<u64> {
+    let (words, bits) = (bits / WORD_BITS, bits % WORD_BITS);
+
+    (0..row.len())
+        .map(|i| {
+            let hi = i.checked_sub(words).map_or(0, |j| row[j]);
+            let lo = i.checked_sub(words + 1).map_or(0, |j| row[j]);
+
+            if bits == 0 {
+                hi
+            } else {
+                (hi << bits) | (lo >> (WORD_BITS - bits))
+            }
+        })
+        .collect()
+}
+
+fn mask_row(row: &mut [u64], width: usize) {
+    for (i, word) in row.iter_mut().enumerate() {
+        let start = i * WORD_BITS;
+
+        if width <= start {
+            *word = 0;
+        } else if width - start < WORD_BITS {
+            *word &= (1 << (width - start)) - 1;
+        }
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, rhs: &BitGrid) {
+        assert_eq!((self.width, self.height), (rhs.width, rhs.height));
+        for (a, b) in self.words.iter_mut().zip(&rhs.words) {
+            *a |= b;
+        }
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, rhs: &BitGrid) {
+        assert_eq!((self.width, self.height), (rhs.width, rhs.height));
+        for (a, b) in self.words.iter_mut().zip(&rhs.words) {
+            *a &= b;
+        }
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", if self.get(x, y) { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive_fold_left(grid: &BitGrid, x: usize) -> BitGrid {
+        let width = x.max(grid.width.saturating_sub(x + 1));
+        let mut folded = BitGrid::new(width, grid.height);
+        for y in 0..grid.height {
+            for i in 0..grid.width {
+                if grid.get(i, y) && i != x {
+                    folded.set(width - i.abs_diff(x), y, true);
+                }
+            }
+        }
+        folded
+    }
+
+    fn pattern(width: usize, height: usize) -> BitGrid {
+        let mut grid = BitGrid::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                grid.set(x, y, (x * 7 + y * 13) % 5 == 0);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn day13_example() {
+        let points = [
+            (6, 10),
+            (0, 14),
+            (9, 10),
+            (0, 3),
+            (10, 4),
+            (4, 11),
+            (6, 0),
+            (6, 12),
+            (4, 1),
+            (0, 13),
+            (10, 12),
+            (3, 4),
+            (3, 0),
+            (8, 4),
+            (1, 10),
+            (2, 14),
+            (8, 10),
+            (9, 0),
+        ];
+        let mut grid = BitGrid::from_points(points);
+        grid.fold_up(7);
+        assert_eq!(grid.count_ones(), 17);
+        grid.fold_left(5);
+        assert_eq!(grid.count_ones(), 16);
+        assert_eq!(
+            grid.to_string(),
+            "#####\n#...#\n#...#\n#...#\n#####\n.....\n.....\n"
+        );
+    }
+
+    #[test]
+    fn folding_past_the_edge_pads() {
+        let mut grid = BitGrid::from_points([(0, 0), (1, 1)]);
+        grid.fold_up(3);
+        assert_eq!((grid.width, grid.height), (2, 3));
+        grid.fold_left(70);
+        assert_eq!((grid.width, grid.height), (70, 3));
+        assert_eq!(grid.count_ones(), 2);
+        assert!(grid.get(1, 1) && !grid.get(69, 2));
+        assert_eq!(grid.to_string().lines().count(), 3);
+    }
+
+    #[test]
+    fn folding_a_bigger_far_side_shifts_the_near_side() {
+        let mut grid = BitGrid::from_points([(10, 0), (0, 0)]);
+        grid.fold_left(3);
+        assert_eq!((grid.width, grid.height), (7, 1));
+        assert_eq!(grid.to_string(), "#...#..\n");
+
+        let mut grid = BitGrid::from_points([(0, 10), (0, 0), (0, 1)]);
+        grid.fold_up(2);
+        assert_eq!((grid.width, grid.height), (1, 8));
+        assert_eq!(grid.to_string(), "#\n.\n.\n.\n.\n.\n#\n#\n");
+    }
+
+    #[test]
+    fn fold_left_matches_naive_across_word_boundaries() {
+        for (width, x) in [
+            (131, 65),
+            (130, 65),
+            (200, 100),
+            (129, 64),
+            (65, 32),
+            (131, 20),
+            (200, 3),
+            (70, 0),
+        ] {
+            let grid = pattern(width, 3);
+            let mut folded = grid.clone();
+            folded.fold_left(x);
+            assert_eq!(folded, naive_fold_left(&grid, x), "width {}", width);
+        }
+    }
+
+    #[test]
+    fn flips_and_popcounts() {
+        let grid = pattern(70, 4);
+        let mut flipped = grid.clone();
+        flipped.flip_horizontal();
+        flipped.flip_vertical();
+        assert_eq!(flipped.count_ones(), grid.count_ones());
+        assert_eq!(flipped.get(69, 3), grid.get(0, 0));
+        assert_eq!(flipped.column_count_ones(0), grid.column_count_ones(69));
+        assert_eq!(flipped.row_count_ones(0), grid.row_count_ones(3));
+    }
+
+    #[test]
+    fn row_and_column_operations() {
+        let mut grid = BitGrid::from_points([(0, 0), (1, 1), (1, 0)]);
+        grid.or_row(1, 0);
+        assert_eq!(grid.row_count_ones(1), 2);
+        grid.and_column(0, 1);
+        assert_eq!(grid.column_count_ones(0), 2);
+        grid.and_row(0, 1);
+        assert_eq!(grid.count_ones(), 4);
+
+        let mut other = BitGrid::new(2, 2);
+        other.set(0, 0, true);
+        grid &= &other;
+        assert_eq!(grid.count_ones(), 1);
+        grid |= &BitGrid::from_points([(1, 1)]);
+        assert_eq!(grid.count_ones(), 2);
+    }
+
+    #[test]
+    fn array2d_round_trip() {
+        let grid = pattern(67, 5);
+        assert_eq!(BitGrid::from_array2d(&grid.to_array2d()), grid);
+    }
+}
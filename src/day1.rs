@@ -1,35 +1,150 @@
-use crate::util::parse_lines;
-use itertools::Itertools;
+use std::{collections::VecDeque, io::BufRead};
 
-const INPUT: &str = include_str!("./day1.txt");
+use anyhow::Context;
 
-fn day1a() {
-    let lines = parse_lines(INPUT);
+const INPUT: &str = include_str!("./day1.txt");
 
-    let total_increases: i32 = lines
-        .into_iter()
-        .tuple_windows()
-        .map(|(a, b)| if b > a { 1 } else { 0 })
-        .sum();
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SonarReport {
+    pub increases: usize,
+    pub decreases: usize,
+    pub plateaus: usize,
+}
 
-    println!("Day 1a: {}", total_increases);
+/// Compares the sums of consecutive sliding windows of depth readings.
+/// Two neighbouring windows share all but one reading, so comparing their sums is the same as
+/// comparing the newest reading with the one `window` readings before it. Only the last
+/// `window` readings are kept in memory.
+pub struct SonarAnalyzer {
+    window: usize,
+    recent: VecDeque<i64>,
+    report: SonarReport,
 }
 
-fn day1b() {
-    let lines = parse_lines(INPUT);
+impl SonarAnalyzer {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window size must be positive");
+
+        SonarAnalyzer {
+            window,
+            recent: VecDeque::with_capacity(window + 1),
+            report: SonarReport::default(),
+        }
+    }
+
+    pub fn push(&mut self, depth: i64) {
+        self.recent.push_back(depth);
+
+        if self.recent.len() > self.window {
+            let dropped = self.recent.pop_front().unwrap();
+
+            match depth.cmp(&dropped) {
+                std::cmp::Ordering::Greater => self.report.increases += 1,
+                std::cmp::Ordering::Less => self.report.decreases += 1,
+                std::cmp::Ordering::Equal => self.report.plateaus += 1,
+            }
+        }
+    }
+
+    pub fn report(&self) -> SonarReport {
+        self.report
+    }
+
+    pub fn analyze(window: usize, depths: impl IntoIterator<Item = i64>) -> SonarReport {
+        let mut analyzer = SonarAnalyzer::new(window);
+
+        for depth in depths {
+            analyzer.push(depth);
+        }
+
+        analyzer.report()
+    }
+
+    /// Reads one depth per line without loading the whole input. Blank lines are skipped.
+    pub fn analyze_reader(window: usize, mut reader: impl BufRead) -> anyhow::Result<SonarReport> {
+        let mut analyzer = SonarAnalyzer::new(window);
+        let mut line = String::new();
+        let mut line_number = 0;
+
+        loop {
+            line.clear();
+            line_number += 1;
+
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
 
-    let total_increases: i32 = lines
-        .into_iter()
-        .tuple_windows()
-        .map(|(a, b, c)| a + b + c)
-        .tuple_windows()
-        .map(|(a, b)| if b > a { 1 } else { 0 })
-        .sum();
+            let trimmed = line.trim();
 
-    println!("Day 1b: {}", total_increases);
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            let depth = trimmed
+                .parse()
+                .with_context(|| format!("invalid depth {:?} on line {}", trimmed, line_number))?;
+            analyzer.push(depth);
+        }
+
+        Ok(analyzer.report())
+    }
+}
+
+fn day1a() {
+    let report = SonarAnalyzer::analyze_reader(1, INPUT.as_bytes()).unwrap();
+    println!("Day 1a: {}", report.increases);
+}
+
+fn day1b() {
+    let report = SonarAnalyzer::analyze_reader(3, INPUT.as_bytes()).unwrap();
+    println!("Day 1b: {}", report.increases);
 }
 
 pub fn day1() {
     day1a();
     day1b();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "199\n200\n208\n210\n200\n207\n240\n269\n260\n263\n";
+
+    #[test]
+    fn single_readings() {
+        let report = SonarAnalyzer::analyze_reader(1, EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(
+            report,
+            SonarReport {
+                increases: 7,
+                decreases: 2,
+                plateaus: 0
+            }
+        );
+    }
+
+    #[test]
+    fn three_reading_windows() {
+        let report = SonarAnalyzer::analyze_reader(3, EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(
+            report,
+            SonarReport {
+                increases: 5,
+                decreases: 1,
+                plateaus: 1
+            }
+        );
+    }
+
+    #[test]
+    fn window_larger_than_input() {
+        let report = SonarAnalyzer::analyze(20, [1, 2, 3]);
+        assert_eq!(report, SonarReport::default());
+    }
+
+    #[test]
+    fn invalid_reading_is_an_error() {
+        assert!(SonarAnalyzer::analyze_reader(1, "1\nfoo\n".as_bytes()).is_err());
+    }
+}
@@ -1,76 +1,291 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, io::BufRead};
 
+use anyhow::Context;
 use itertools::Itertools;
 
 const INPUT: &str = include_str!("./day10.txt");
 
+/// A kind of chunk, as an index into its `DelimiterSet`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ChunkType {
-    Paren,
-    Bracket,
-    Brace,
-    Angle,
+struct ChunkType(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Delimiter {
+    opener: char,
+    /// May be the same as the opener, like with quotes. Such chunks can't nest directly inside
+    /// themselves, since the second quote closes the first.
+    closer: char,
+    corruption_score: usize,
+    completion_score: usize,
+}
+
+impl Delimiter {
+    fn new(opener: char, closer: char, corruption_score: usize, completion_score: usize) -> Self {
+        Delimiter {
+            opener,
+            closer,
+            corruption_score,
+            completion_score,
+        }
+    }
+}
+
+/// The bracket language being parsed.
+#[derive(Debug, Clone)]
+struct DelimiterSet {
+    delimiters: Vec<Delimiter>,
+    /// Completion scores are read as digits in this base.
+    completion_base: usize,
+}
+
+impl DelimiterSet {
+    fn new(delimiters: Vec<Delimiter>, completion_base: usize) -> anyhow::Result<Self> {
+        let chars = delimiters
+            .iter()
+            .flat_map(|d| [d.opener, d.closer].into_iter().dedup())
+            .collect_vec();
+
+        anyhow::ensure!(
+            chars.iter().all_unique(),
+            "delimiters can't share characters"
+        );
+
+        Ok(DelimiterSet {
+            delimiters,
+            completion_base,
+        })
+    }
+
+    fn standard() -> Self {
+        DelimiterSet::new(
+            vec![
+                Delimiter::new('(', ')', 3, 1),
+                Delimiter::new('[', ']', 57, 2),
+                Delimiter::new('{', '}', 1197, 3),
+                Delimiter::new('<', '>', 25137, 4),
+            ],
+            5,
+        )
+        .unwrap()
+    }
+
+    fn get(&self, kind: ChunkType) -> &Delimiter {
+        &self.delimiters[kind.0]
+    }
+
+    fn kind_of(&self, c: char) -> Option<ChunkType> {
+        self.delimiters
+            .iter()
+            .position(|d| d.opener == c || d.closer == c)
+            .map(ChunkType)
+    }
+
+    fn token(&self, c: char) -> Token {
+        match self.kind_of(c) {
+            Some(kind) => {
+                let delimiter = self.get(kind);
+                if delimiter.opener == delimiter.closer {
+                    Token::Either(kind)
+                } else if delimiter.opener == c {
+                    Token::L(kind)
+                } else {
+                    Token::R(kind)
+                }
+            }
+            None => Token::Unknown(c),
+        }
+    }
+
+    /// Tokens with their byte offsets in the line.
+    fn lex(&self, s: &str) -> Vec<(usize, Token)> {
+        s.char_indices()
+            .map(|(offset, c)| (offset, self.token(c)))
+            .collect_vec()
+    }
+
+    fn corruption_score(&self, diagnostic: &Diagnostic) -> usize {
+        diagnostic
+            .found()
+            .map_or(0, |kind| self.get(kind).corruption_score)
+    }
+
+    /// `None` if the score doesn't fit in a usize, which deep enough nesting always gets to.
+    fn completion_score(&self, completion: &[ChunkType]) -> Option<usize> {
+        completion.iter().try_fold(0usize, |score, &kind| {
+            score
+                .checked_mul(self.completion_base)?
+                .checked_add(self.get(kind).completion_score)
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 enum Token {
     L(ChunkType),
     R(ChunkType),
+    /// A delimiter that both opens and closes.
+    Either(ChunkType),
+    Unknown(char),
 }
 
-#[derive(Debug, Clone)]
+impl Token {
+    /// An `Either` closes the chunk it's in if it's of the same kind, and opens a new one
+    /// otherwise.
+    fn resolve(self, enclosing: Option<ChunkType>) -> Token {
+        match self {
+            Token::Either(kind) if enclosing == Some(kind) => Token::R(kind),
+            Token::Either(kind) => Token::L(kind),
+            token => token,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Expression {
     Chunk(ChunkType, Vec<Expression>),
 }
 
-fn lex(s: &str) -> Vec<Token> {
-    s.chars()
-        .map(|c| match c {
-            '(' => Token::L(ChunkType::Paren),
-            ')' => Token::R(ChunkType::Paren),
-            '[' => Token::L(ChunkType::Bracket),
-            ']' => Token::R(ChunkType::Bracket),
-            '{' => Token::L(ChunkType::Brace),
-            '}' => Token::R(ChunkType::Brace),
-            '<' => Token::L(ChunkType::Angle),
-            '>' => Token::R(ChunkType::Angle),
-            _ => panic!("unexpected character: {}", c),
-        })
-        .collect_vec()
+/// What went wrong in a corrupted line. Offsets are in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Diagnostic {
+    /// A closer that doesn't match the innermost open chunk.
+    Mismatched {
+        found: ChunkType,
+        offset: usize,
+        expected: ChunkType,
+        opener: usize,
+    },
+    /// A closer without any open chunk.
+    Unopened {
+        found: ChunkType,
+        offset: usize,
+    },
+    UnknownCharacter {
+        character: char,
+        offset: usize,
+    },
 }
 
-#[derive(Debug, Clone)]
+impl Diagnostic {
+    fn from_token(offset: usize, token: Token, open: Option<(usize, ChunkType)>) -> Self {
+        match (token, open) {
+            (Token::R(found), Some((opener, expected))) => Diagnostic::Mismatched {
+                found,
+                offset,
+                expected,
+                opener,
+            },
+            (Token::R(found), None) => Diagnostic::Unopened { found, offset },
+            (Token::Unknown(character), _) => Diagnostic::UnknownCharacter { character, offset },
+            (Token::L(_) | Token::Either(_), _) => unreachable!("openers are never an error"),
+        }
+    }
+
+    /// The closer that corrupted the line, if it was one.
+    fn found(&self) -> Option<ChunkType> {
+        match *self {
+            Diagnostic::Mismatched { found, .. } | Diagnostic::Unopened { found, .. } => {
+                Some(found)
+            }
+            Diagnostic::UnknownCharacter { .. } => None,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        match *self {
+            Diagnostic::Mismatched { offset, .. }
+            | Diagnostic::Unopened { offset, .. }
+            | Diagnostic::UnknownCharacter { offset, .. } => offset,
+        }
+    }
+
+    fn message(&self, delimiters: &DelimiterSet) -> String {
+        match *self {
+            Diagnostic::Mismatched {
+                found, expected, ..
+            } => format!(
+                "expected `{}`, found `{}`",
+                delimiters.get(expected).closer,
+                delimiters.get(found).closer
+            ),
+            Diagnostic::Unopened { found, .. } => {
+                format!(
+                    "unexpected closing delimiter `{}`",
+                    delimiters.get(found).closer
+                )
+            }
+            Diagnostic::UnknownCharacter { character, .. } => {
+                format!("unknown character `{}`", character)
+            }
+        }
+    }
+
+    /// Renders the diagnostic under the line like a compiler would, with a `^` under the
+    /// offending character and a `-` under the chunk it should have closed.
+    fn render(&self, line: &str, delimiters: &DelimiterSet) -> String {
+        let column = |offset: usize| line[..offset].chars().count();
+
+        let mut markers = vec![' '; column(self.offset()) + 1];
+        markers[column(self.offset())] = '^';
+
+        let label = match *self {
+            Diagnostic::Mismatched {
+                expected, opener, ..
+            } => {
+                markers[column(opener)] = '-';
+                let expected = delimiters.get(expected);
+                format!(
+                    " expected `{}` to close `{}`",
+                    expected.closer, expected.opener
+                )
+            }
+            _ => String::new(),
+        };
+
+        format!(
+            "error: {}\n{}\n{}{}\n",
+            self.message(delimiters),
+            line,
+            markers.into_iter().collect::<String>(),
+            label
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum ParseResult<T> {
     Valid(T),
     Incomplete,
-    Corrupted(ChunkType),
+    Corrupted(Diagnostic),
 }
 
 fn parse(
-    tokens: &mut VecDeque<Token>,
+    tokens: &mut VecDeque<(usize, Token)>,
+    enclosing: Option<ChunkType>,
     completion: &mut Option<Vec<ChunkType>>,
 ) -> ParseResult<Option<Expression>> {
     let next = tokens.front().copied();
     let next = match next {
-        Some(t) => t,
+        Some((offset, token)) => (offset, token.resolve(enclosing)),
         None => return ParseResult::Valid(None),
     };
 
     match next {
-        Token::R(_) => return ParseResult::Valid(None),
-        Token::L(open_type) => {
+        (_, Token::R(_) | Token::Unknown(_)) => ParseResult::Valid(None),
+        (_, Token::Either(_)) => unreachable!("resolved above"),
+        (open_offset, Token::L(open_type)) => {
             tokens.pop_front().unwrap();
 
             let mut expressions = Vec::new();
 
             loop {
-                let inner = parse(tokens, completion);
+                let inner = parse(tokens, Some(open_type), completion);
 
                 match inner {
                     ParseResult::Valid(Some(expr)) => expressions.push(expr),
                     ParseResult::Valid(None) => {}
-                    ParseResult::Corrupted(ch) => {
-                        return ParseResult::Corrupted(ch);
+                    ParseResult::Corrupted(diagnostic) => {
+                        return ParseResult::Corrupted(diagnostic);
                     }
                     ParseResult::Incomplete => {
                         if let Some(completion) = completion {
@@ -81,15 +296,21 @@ fn parse(
                     }
                 }
 
-                let next = tokens.front().copied();
+                let next = tokens
+                    .front()
+                    .map(|&(offset, token)| (offset, token.resolve(Some(open_type))));
                 match next {
-                    Some(Token::R(ch)) if ch == open_type => {
+                    Some((_, Token::R(ch))) if ch == open_type => {
                         break;
                     }
-                    Some(Token::R(ch)) => {
-                        return ParseResult::Corrupted(ch);
+                    Some((offset, token @ (Token::R(_) | Token::Unknown(_)))) => {
+                        return ParseResult::Corrupted(Diagnostic::from_token(
+                            offset,
+                            token,
+                            Some((open_offset, open_type)),
+                        ));
                     }
-                    Some(Token::L(_)) => {}
+                    Some((_, Token::L(_) | Token::Either(_))) => {}
                     None => {
                         if let Some(completion) = completion {
                             completion.push(open_type);
@@ -107,41 +328,174 @@ fn parse(
     }
 }
 
-fn parse_line(line: &str, completion: &mut Option<Vec<ChunkType>>) -> ParseResult<Vec<Expression>> {
-    let mut tokens = VecDeque::from(lex(line));
+fn parse_line(
+    line: &str,
+    delimiters: &DelimiterSet,
+    completion: &mut Option<Vec<ChunkType>>,
+) -> ParseResult<Vec<Expression>> {
+    let mut tokens = VecDeque::from(delimiters.lex(line));
     let mut expressions: Vec<Expression> = Vec::new();
 
     loop {
-        let next = parse(&mut tokens, completion);
+        let next = parse(&mut tokens, None, completion);
         match next {
             ParseResult::Valid(Some(expr)) => expressions.push(expr),
             ParseResult::Valid(None) => break,
             ParseResult::Incomplete => {
                 return ParseResult::Incomplete;
             }
-            ParseResult::Corrupted(ch) => {
-                return ParseResult::Corrupted(ch);
+            ParseResult::Corrupted(diagnostic) => {
+                return ParseResult::Corrupted(diagnostic);
             }
         }
     }
 
     match tokens.front() {
         None => ParseResult::Valid(expressions),
-        Some(Token::R(ch)) => ParseResult::Corrupted(*ch),
-        Some(Token::L(_)) => unreachable!(),
+        Some(&(offset, token)) => {
+            ParseResult::Corrupted(Diagnostic::from_token(offset, token, None))
+        }
+    }
+}
+
+/// Everything a recovering parse found on a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Recovered {
+    expressions: Vec<Expression>,
+    diagnostics: Vec<Diagnostic>,
+    /// Closers still missing at the end of the line, innermost first.
+    completion: Vec<ChunkType>,
+}
+
+/// Parses a sequence of chunks until the end of the line or a closer for one of the `open`
+/// chunks, recovering from anything else. Closers that don't match any open chunk are skipped,
+/// and chunks closed early by an outer closer get their missing closer inserted.
+fn parse_recovering(
+    tokens: &mut VecDeque<(usize, Token)>,
+    open: &mut Vec<(usize, ChunkType)>,
+    recovered: &mut Recovered,
+) -> Vec<Expression> {
+    let mut expressions = Vec::new();
+
+    loop {
+        let enclosing = open.last().copied();
+        let (offset, token) = match tokens.front() {
+            Some(&(offset, token)) => (offset, token.resolve(enclosing.map(|(_, kind)| kind))),
+            None => return expressions,
+        };
+
+        match token {
+            Token::L(kind) => {
+                tokens.pop_front();
+
+                open.push((offset, kind));
+                let children = parse_recovering(tokens, open, recovered);
+                open.pop();
+
+                let next = tokens
+                    .front()
+                    .map(|&(offset, token)| (offset, token.resolve(Some(kind))));
+                match next {
+                    Some((_, Token::R(closer))) if closer == kind => {
+                        tokens.pop_front();
+                    }
+                    // Only a closer for an outer chunk stops the children early
+                    Some((closer_offset, token)) => recovered.diagnostics.push(
+                        Diagnostic::from_token(closer_offset, token, Some((offset, kind))),
+                    ),
+                    None => recovered.completion.push(kind),
+                }
+
+                expressions.push(Expression::Chunk(kind, children));
+            }
+            Token::R(kind) if open.iter().any(|&(_, open)| open == kind) => return expressions,
+            Token::R(_) | Token::Unknown(_) => {
+                tokens.pop_front();
+                recovered
+                    .diagnostics
+                    .push(Diagnostic::from_token(offset, token, enclosing));
+            }
+            Token::Either(_) => unreachable!("resolved above"),
+        }
+    }
+}
+
+fn parse_line_recovering(line: &str, delimiters: &DelimiterSet) -> Recovered {
+    let mut tokens = VecDeque::from(delimiters.lex(line));
+    let mut recovered = Recovered {
+        expressions: Vec::new(),
+        diagnostics: Vec::new(),
+        completion: Vec::new(),
+    };
+
+    recovered.expressions = parse_recovering(&mut tokens, &mut Vec::new(), &mut recovered);
+    recovered
+}
+
+/// Checks a line like `parse_line` without building any expressions. Open chunks are kept on an
+/// explicit stack instead of the call stack, so nesting depth is only limited by memory.
+fn validate(
+    line: &str,
+    delimiters: &DelimiterSet,
+    completion: &mut Option<Vec<ChunkType>>,
+) -> ParseResult<()> {
+    let mut open: Vec<(usize, ChunkType)> = Vec::new();
+
+    for (offset, c) in line.char_indices() {
+        let enclosing = open.last().copied();
+
+        match delimiters.token(c).resolve(enclosing.map(|(_, kind)| kind)) {
+            Token::L(kind) => open.push((offset, kind)),
+            Token::R(kind) if enclosing.is_some_and(|(_, open)| open == kind) => {
+                open.pop();
+            }
+            token => {
+                return ParseResult::Corrupted(Diagnostic::from_token(offset, token, enclosing))
+            }
+        }
+    }
+
+    if open.is_empty() {
+        return ParseResult::Valid(());
     }
+
+    if let Some(completion) = completion {
+        completion.extend(open.iter().rev().map(|&(_, kind)| kind));
+    }
+
+    ParseResult::Incomplete
+}
+
+/// Validates each line of the reader as it's read, yielding the result with the completion.
+fn validate_reader<'a>(
+    mut reader: impl BufRead + 'a,
+    delimiters: &'a DelimiterSet,
+) -> impl Iterator<Item = anyhow::Result<(ParseResult<()>, Vec<ChunkType>)>> + 'a {
+    let mut line = String::new();
+
+    std::iter::from_fn(move || {
+        line.clear();
+
+        match reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                let mut completion = Some(Vec::new());
+                let result = validate(line.trim_end(), delimiters, &mut completion);
+                Some(Ok((result, completion.unwrap())))
+            }
+            Err(err) => Some(Err(err.into())),
+        }
+    })
 }
 
 pub fn a() {
+    let delimiters = DelimiterSet::standard();
     let mut score = 0;
 
-    for line in INPUT.lines() {
-        let parsed = parse_line(line, &mut None);
-        let value = match parsed {
-            ParseResult::Corrupted(ChunkType::Paren) => 3,
-            ParseResult::Corrupted(ChunkType::Bracket) => 57,
-            ParseResult::Corrupted(ChunkType::Brace) => 1197,
-            ParseResult::Corrupted(ChunkType::Angle) => 25137,
+    for validation in validate_reader(INPUT.as_bytes(), &delimiters) {
+        let (result, _) = validation.unwrap();
+        let value = match result {
+            ParseResult::Corrupted(diagnostic) => delimiters.corruption_score(&diagnostic),
             _ => 0,
         };
 
@@ -151,39 +505,411 @@ pub fn a() {
     println!("Day10a: {}", score);
 }
 
-fn score_completion(completion: &[ChunkType]) -> usize {
-    let mut score = 0;
+/// The middle completion score of the incomplete lines.
+fn middle_completion_score(
+    reader: impl BufRead,
+    delimiters: &DelimiterSet,
+) -> anyhow::Result<usize> {
+    let mut scores = Vec::new();
 
-    for ch in completion.iter().copied() {
-        score *= 5;
+    for (i, validation) in validate_reader(reader, delimiters).enumerate() {
+        let (_, completion) = validation?;
+        let value = delimiters
+            .completion_score(&completion)
+            .with_context(|| format!("line {}: completion score overflows", i + 1))?;
 
-        score += match ch {
-            ChunkType::Paren => 1,
-            ChunkType::Bracket => 2,
-            ChunkType::Brace => 3,
-            ChunkType::Angle => 4,
-        };
+        if value > 0 {
+            scores.push(value);
+        }
     }
 
-    score
+    anyhow::ensure!(!scores.is_empty(), "no incomplete lines");
+    scores.sort_unstable();
+
+    Ok(scores[scores.len() / 2])
 }
 
 pub fn b() {
-    let mut scores = Vec::new();
+    let delimiters = DelimiterSet::standard();
+    let middle_score = middle_completion_score(INPUT.as_bytes(), &delimiters).unwrap();
+
+    println!("Day10b: {}", middle_score);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::Rng;
 
-    for line in INPUT.lines() {
+    fn diagnose(line: &str) -> Diagnostic {
+        match parse_line(line, &DelimiterSet::standard(), &mut None) {
+            ParseResult::Corrupted(diagnostic) => diagnostic,
+            other => panic!("{} should be corrupted, got {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn example_corruption() {
+        let line = "{([(<{}[<>[]}>{[]{[(<()>";
+        assert_eq!(
+            diagnose(line),
+            Diagnostic::Mismatched {
+                found: ChunkType(2),
+                offset: 12,
+                expected: ChunkType(1),
+                opener: 7
+            }
+        );
+        assert_eq!(
+            diagnose(line).render(line, &DelimiterSet::standard()),
+            "error: expected `]`, found `}`\n\
+             {([(<{}[<>[]}>{[]{[(<()>\n\
+             \x20      -    ^ expected `]` to close `[`\n"
+        );
+
+        let found = [
+            "[[<[([]))<([[{}[[()]]]",
+            "[<(<(<(<{}))><([]([]()",
+            "<{([([[(<>()){}]>(<<{{",
+        ]
+        .into_iter()
+        .map(|line| diagnose(line).found().unwrap())
+        .collect_vec();
+        assert_eq!(found, [ChunkType(0), ChunkType(0), ChunkType(3)]);
+    }
+
+    #[test]
+    fn unopened_closer() {
+        let line = "()]";
+        assert_eq!(
+            diagnose(line),
+            Diagnostic::Unopened {
+                found: ChunkType(1),
+                offset: 2
+            }
+        );
+        assert_eq!(
+            diagnose(line).render(line, &DelimiterSet::standard()),
+            "error: unexpected closing delimiter `]`\n()]\n  ^\n"
+        );
+    }
+
+    #[test]
+    fn unknown_characters() {
+        assert_eq!(
+            diagnose("(<x>)"),
+            Diagnostic::UnknownCharacter {
+                character: 'x',
+                offset: 2
+            }
+        );
+        assert_eq!(
+            diagnose("é()"),
+            Diagnostic::UnknownCharacter {
+                character: 'é',
+                offset: 0
+            }
+        );
+        assert_eq!(
+            diagnose("(é]").render("(é]", &DelimiterSet::standard()),
+            "error: unknown character `é`\n(é]\n ^\n"
+        );
+
+        // Whatever comes first is reported
+        assert_eq!(diagnose("(]x").found(), Some(ChunkType(1)));
+        assert_eq!(diagnose("x(]").found(), None);
+    }
+
+    fn custom() -> DelimiterSet {
+        DelimiterSet::new(
+            vec![
+                Delimiter::new('(', ')', 1, 1),
+                Delimiter::new('«', '»', 10, 2),
+                Delimiter::new('"', '"', 100, 3),
+            ],
+            4,
+        )
+        .unwrap()
+    }
+
+    fn check(line: &str, delimiters: &DelimiterSet) -> (usize, usize) {
         let mut completion = Some(Vec::new());
-        parse_line(line, &mut completion);
-        let value = score_completion(completion.as_ref().unwrap());
+        let corruption = match parse_line(line, delimiters, &mut completion) {
+            ParseResult::Corrupted(diagnostic) => delimiters.corruption_score(&diagnostic),
+            _ => 0,
+        };
 
-        if value > 0 {
-            scores.push(value);
+        (
+            corruption,
+            delimiters.completion_score(&completion.unwrap()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn standard_scores() {
+        let delimiters = DelimiterSet::standard();
+        assert_eq!(check("{([(<{}[<>[]}>{[]{[(<()>", &delimiters), (1197, 0));
+        assert_eq!(check("<{([{{}}[<[[[<>{}]]]>[]]", &delimiters), (0, 294));
+        assert_eq!(check("()[]{}<>", &delimiters), (0, 0));
+    }
+
+    #[test]
+    fn custom_delimiters() {
+        let delimiters = custom();
+
+        assert_eq!(check("«()»", &delimiters), (0, 0));
+        assert_eq!(check("(«)", &delimiters), (1, 0));
+        assert_eq!(check("(«\"", &delimiters), (0, 3 * 16 + 2 * 4 + 1));
+
+        // Quotes close themselves, but can hold other quotes inside other chunks
+        assert_eq!(check("\"(\"\")\"", &delimiters), (0, 0));
+        assert_eq!(check("\"\"\"", &delimiters), (0, 3));
+        assert_eq!(check("(\")", &delimiters), (1, 0));
+
+        // Standard brackets are unknown here, and don't score
+        assert_eq!(check("([)", &delimiters), (0, 0));
+    }
+
+    #[test]
+    fn custom_diagnostics() {
+        let delimiters = custom();
+        let line = "«(»";
+
+        // Offsets are in bytes, but the markers line up with characters
+        match parse_line(line, &delimiters, &mut None) {
+            ParseResult::Corrupted(diagnostic) => {
+                assert_eq!(diagnostic.offset(), 3);
+                assert_eq!(
+                    diagnostic.render(line, &delimiters),
+                    "error: expected `)`, found `»`\n«(»\n -^ expected `)` to close `(`\n"
+                );
+            }
+            other => panic!("expected corruption, got {:?}", other),
         }
+
+        let clash = DelimiterSet::new(
+            vec![
+                Delimiter::new('(', ')', 1, 1),
+                Delimiter::new(')', '(', 1, 1),
+            ],
+            5,
+        );
+        assert!(clash.is_err());
     }
 
-    scores.sort_unstable();
+    fn chunk(kind: usize, children: Vec<Expression>) -> Expression {
+        Expression::Chunk(ChunkType(kind), children)
+    }
 
-    let middle_score = scores[scores.len() / 2];
+    #[test]
+    fn recovery_finds_every_mismatch() {
+        let recovered = parse_line_recovering("(]<}>)", &DelimiterSet::standard());
+
+        assert_eq!(
+            recovered.diagnostics,
+            vec![
+                Diagnostic::Mismatched {
+                    found: ChunkType(1),
+                    offset: 1,
+                    expected: ChunkType(0),
+                    opener: 0
+                },
+                Diagnostic::Mismatched {
+                    found: ChunkType(2),
+                    offset: 3,
+                    expected: ChunkType(3),
+                    opener: 2
+                },
+            ]
+        );
+        assert_eq!(
+            recovered.expressions,
+            vec![chunk(0, vec![chunk(3, vec![])])]
+        );
+        assert!(recovered.completion.is_empty());
+    }
 
-    println!("Day10b: {}", middle_score);
+    #[test]
+    fn recovery_inserts_missing_closers() {
+        let delimiters = DelimiterSet::standard();
+
+        // The `}` closes the brace, so the paren inside must have been missing its closer
+        let recovered = parse_line_recovering("{(}<x", &delimiters);
+        assert_eq!(
+            recovered.diagnostics,
+            vec![
+                Diagnostic::Mismatched {
+                    found: ChunkType(2),
+                    offset: 2,
+                    expected: ChunkType(0),
+                    opener: 1
+                },
+                Diagnostic::UnknownCharacter {
+                    character: 'x',
+                    offset: 4
+                },
+            ]
+        );
+        assert_eq!(
+            recovered.expressions,
+            vec![chunk(2, vec![chunk(0, vec![])]), chunk(3, vec![])]
+        );
+        assert_eq!(recovered.completion, vec![ChunkType(3)]);
+
+        let recovered = parse_line_recovering("])", &delimiters);
+        assert_eq!(recovered.diagnostics.len(), 2);
+        assert!(recovered.expressions.is_empty());
+    }
+
+    #[test]
+    fn recovery_agrees_with_strict_parser() {
+        let delimiters = DelimiterSet::standard();
+
+        for line in INPUT.lines() {
+            let mut completion = Some(Vec::new());
+            let strict = parse_line(line, &delimiters, &mut completion);
+            let recovered = parse_line_recovering(line, &delimiters);
+
+            match strict {
+                ParseResult::Corrupted(diagnostic) => {
+                    assert_eq!(recovered.diagnostics[0], diagnostic)
+                }
+                ParseResult::Incomplete => {
+                    assert!(recovered.diagnostics.is_empty());
+                    assert_eq!(recovered.completion, completion.unwrap());
+                }
+                ParseResult::Valid(expressions) => {
+                    assert!(recovered.diagnostics.is_empty());
+                    assert_eq!(recovered.expressions, expressions);
+                }
+            }
+        }
+    }
+
+    fn shape<T>(result: ParseResult<T>) -> ParseResult<()> {
+        match result {
+            ParseResult::Valid(_) => ParseResult::Valid(()),
+            ParseResult::Incomplete => ParseResult::Incomplete,
+            ParseResult::Corrupted(diagnostic) => ParseResult::Corrupted(diagnostic),
+        }
+    }
+
+    fn assert_validators_agree(line: &str, delimiters: &DelimiterSet) {
+        let mut recursive_completion = Some(Vec::new());
+        let recursive = parse_line(line, delimiters, &mut recursive_completion);
+
+        let mut completion = Some(Vec::new());
+        assert_eq!(
+            validate(line, delimiters, &mut completion),
+            shape(recursive),
+            "{}",
+            line
+        );
+        assert_eq!(completion, recursive_completion, "{}", line);
+    }
+
+    #[test]
+    fn validator_matches_recursive_parser() {
+        let standard = DelimiterSet::standard();
+        for line in INPUT.lines() {
+            assert_validators_agree(line, &standard);
+        }
+
+        // Random lines over both delimiter sets, mostly openers so some of them are valid
+        let mut rng = Rng::new(10);
+        for delimiters in [standard, custom()] {
+            let chars = delimiters
+                .delimiters
+                .iter()
+                .flat_map(|d| [d.opener, d.closer])
+                .chain(['x'])
+                .collect_vec();
+
+            for _ in 0..2000 {
+                let line = (0..rng.below(12))
+                    .map(|_| chars[rng.below(chars.len())])
+                    .collect::<String>();
+                assert_validators_agree(&line, &delimiters);
+            }
+        }
+    }
+
+    #[test]
+    fn validator_handles_deep_nesting() {
+        let delimiters = DelimiterSet::standard();
+        let depth = 200_000;
+
+        let valid = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(
+            validate(&valid, &delimiters, &mut None),
+            ParseResult::Valid(())
+        );
+
+        let mut completion = Some(Vec::new());
+        let incomplete = "[".repeat(depth);
+        assert_eq!(
+            validate(&incomplete, &delimiters, &mut completion),
+            ParseResult::Incomplete
+        );
+        assert_eq!(completion.unwrap().len(), depth);
+
+        let corrupted = format!("{}>", "{".repeat(depth));
+        assert_eq!(
+            validate(&corrupted, &delimiters, &mut None),
+            ParseResult::Corrupted(Diagnostic::Mismatched {
+                found: ChunkType(3),
+                offset: depth,
+                expected: ChunkType(2),
+                opener: depth - 1
+            })
+        );
+    }
+
+    #[test]
+    fn validating_a_reader() {
+        let delimiters = DelimiterSet::standard();
+        let results = validate_reader("()\n(]\n[<\n".as_bytes(), &delimiters)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                (ParseResult::Valid(()), vec![]),
+                (
+                    ParseResult::Corrupted(Diagnostic::Mismatched {
+                        found: ChunkType(1),
+                        offset: 1,
+                        expected: ChunkType(0),
+                        opener: 0
+                    }),
+                    vec![]
+                ),
+                (ParseResult::Incomplete, vec![ChunkType(3), ChunkType(1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn middle_completion_score_of_example() {
+        let example =
+            "[({(<(())[]>[[{[]{<()<>>\n[(()[<>])]({[<{<<[]>>(\n{([(<{}[<>[]}>{[]{[(<()>\n\
+                       (((({<>}<{<{<>}{[]{[]{}\n[[<[([]))<([[{}[[()]]]\n[{[{({}]{}}([{[{{{}}([]\n\
+                       {<[[]]>}<{[{[{[]{()[[[]\n[<(<(<(<{}))><([]([]()\n<{([([[(<>()){}]>(<<{{\n\
+                       <{([{{}}[<[[[<>{}]]]>[]]\n";
+        let delimiters = DelimiterSet::standard();
+        assert_eq!(
+            middle_completion_score(example.as_bytes(), &delimiters).unwrap(),
+            288957
+        );
+    }
+
+    #[test]
+    fn deep_completion_score_overflows() {
+        let delimiters = DelimiterSet::standard();
+        let deep = format!("()\n{}\n<\n", "[".repeat(100_000));
+
+        let err = middle_completion_score(deep.as_bytes(), &delimiters).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: completion score overflows");
+    }
 }
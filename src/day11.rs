@@ -1,8 +1,11 @@
-use crate::array2d::Array2D;
+use crate::{
+    array2d::Array2D,
+    util::{iterate_until, Iteration},
+};
 
 const INPUT: &str = include_str!("./day11.txt");
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum OctopusState {
     Charging(u8),
     Flashed,
@@ -11,11 +14,8 @@ enum OctopusState {
 fn flash_octopus(world: &mut Array2D<OctopusState>, i: usize, flashes: &mut usize) {
     let octopus = &mut world.data[i];
 
-    match octopus {
-        &mut OctopusState::Flashed => {
-            return;
-        }
-        _ => {}
+    if let OctopusState::Flashed = octopus {
+        return;
     }
 
     *flashes += 1;
@@ -45,85 +45,57 @@ fn flash_octopus(world: &mut Array2D<OctopusState>, i: usize, flashes: &mut usiz
     }
 }
 
-pub fn a() {
-    let mut input = Array2D::from_string(INPUT).map(OctopusState::Charging);
-    let mut steps = 0;
+fn step(world: &mut Array2D<OctopusState>) -> usize {
     let mut flashes = 0;
 
-    while steps < 100 {
-        for i in 0..input.data.len() {
-            let octopus = &mut input.data[i];
-
-            match octopus {
-                OctopusState::Charging(n) => {
-                    *n += 1;
+    for i in 0..world.data.len() {
+        if let OctopusState::Charging(n) = &mut world.data[i] {
+            *n += 1;
 
-                    if *n > 9 {
-                        flash_octopus(&mut input, i, &mut flashes);
-                    }
-                }
-                _ => {}
+            if *n > 9 {
+                flash_octopus(world, i, &mut flashes);
             }
         }
+    }
 
-        for i in 0..input.data.len() {
-            let octopus = &mut input.data[i];
-
-            match octopus {
-                OctopusState::Flashed => {
-                    *octopus = OctopusState::Charging(0);
-                }
-                _ => {}
-            }
+    for octopus in world.data.iter_mut() {
+        if let OctopusState::Flashed = octopus {
+            *octopus = OctopusState::Charging(0);
         }
-
-        steps += 1;
     }
 
-    println!("Day11a: {}", flashes);
+    flashes
 }
 
-pub fn b() {
+pub fn a() {
     let mut input = Array2D::from_string(INPUT).map(OctopusState::Charging);
-    let mut steps = 0;
-
-    loop {
-        for i in 0..input.data.len() {
-            let octopus = &mut input.data[i];
-
-            match octopus {
-                OctopusState::Charging(n) => {
-                    *n += 1;
-
-                    if *n > 9 {
-                        flash_octopus(&mut input, i, &mut 0);
-                    }
-                }
-                _ => {}
-            }
-        }
-
-        let mut all_flashed = true;
-
-        for i in 0..input.data.len() {
-            let octopus = &mut input.data[i];
+    let mut flashes = 0;
 
-            match octopus {
-                OctopusState::Flashed => {
-                    *octopus = OctopusState::Charging(0);
-                }
-                OctopusState::Charging(_) => {
-                    all_flashed = false;
-                }
-            }
-        }
+    for _ in 0..100 {
+        flashes += step(&mut input);
+    }
 
-        steps += 1;
+    println!("Day11a: {}", flashes);
+}
 
-        if all_flashed {
-            break;
-        }
+pub fn b() {
+    let input = Array2D::from_string(INPUT).map(OctopusState::Charging);
+
+    let iteration = iterate_until(
+        input,
+        |world| {
+            let mut next = world.clone();
+            step(&mut next);
+            next
+        },
+        |world| world.iter().all(|o| o == OctopusState::Charging(0)),
+    );
+
+    match iteration {
+        Iteration::Finished { steps, .. } => println!("Day11b: {}", steps),
+        Iteration::Cycle { cycle, .. } => println!(
+            "Day11b: octopuses never synchronize (cycle of {} from step {})",
+            cycle.period, cycle.start
+        ),
     }
-
-    println!("Day11b: {}", steps);
 }
@@ -1,8 +1,8 @@
-use std::collections::HashSet;
-
 use itertools::Itertools;
 use scan_fmt::scan_fmt;
 
+use crate::bitgrid::BitGrid;
+
 const INPUT: &str = include_str!("./day13.txt");
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -17,7 +17,7 @@ struct Input {
     folds: Vec<(Axis, i32)>,
 }
 
-type World = HashSet<(i32, i32)>;
+type World = BitGrid;
 
 fn read_input() -> Input {
     let (points, folds) = INPUT.split_once("\n\n").unwrap();
@@ -48,48 +48,45 @@ fn read_input() -> Input {
     Input { points, folds }
 }
 
-fn fold_points(world: World, axis: Axis, value: i32) -> World {
-    let mut new_world = HashSet::new();
+/// The paper is folded exactly in half each time, so the first fold along each axis gives the
+/// paper size even when no dot lies on the last row or column.
+fn to_world(points: Vec<(i32, i32)>, folds: &[(Axis, i32)]) -> World {
+    let paper_size = |axis: Axis, coordinate: fn(&(i32, i32)) -> i32| {
+        let from_folds = folds
+            .iter()
+            .find(|(a, _)| *a == axis)
+            .map(|(_, v)| 2 * v + 1);
+        let from_points = points.iter().map(|p| coordinate(p) + 1).max();
+        from_folds.max(from_points).unwrap_or(0) as usize
+    };
 
-    for point in world.into_iter() {
-        let point = fold_point(axis, value, point);
-        new_world.insert(point);
+    let mut world = BitGrid::new(paper_size(Axis::X, |p| p.0), paper_size(Axis::Y, |p| p.1));
+    for (x, y) in points {
+        world.set(x as usize, y as usize, true);
     }
-
-    new_world
+    world
 }
 
-fn fold_point(axis: Axis, value: i32, (x, y): (i32, i32)) -> (i32, i32) {
-    let point = match axis {
-        Axis::X => {
-            if x < value {
-                (x, y)
-            } else {
-                let x = value - (x - value);
-                (x, y)
-            }
-        }
-        Axis::Y => {
-            if y < value {
-                (x, y)
-            } else {
-                let y = value - (y - value);
-                (x, y)
-            }
-        }
-    };
-    point
+fn fold_world(world: &mut World, axis: Axis, value: i32) {
+    match axis {
+        Axis::X => world.fold_left(value as usize),
+        Axis::Y => world.fold_up(value as usize),
+    }
 }
 
+// Only the dots' bounding box, with blanks as spaces
 pub fn print_world(world: &World) {
-    let min_x = world.iter().map(|(x, _)| *x).min().unwrap();
-    let max_x = world.iter().map(|(x, _)| *x).max().unwrap();
-    let min_y = world.iter().map(|(_, y)| *y).min().unwrap();
-    let max_y = world.iter().map(|(_, y)| *y).max().unwrap();
+    let dots = (0..world.height)
+        .flat_map(|y| (0..world.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| world.get(x, y))
+        .collect_vec();
+
+    let (min_x, max_x) = dots.iter().map(|&(x, _)| x).minmax().into_option().unwrap();
+    let (min_y, max_y) = dots.iter().map(|&(_, y)| y).minmax().into_option().unwrap();
 
     for y in min_y..=max_y {
         for x in min_x..=max_x {
-            if world.contains(&(x, y)) {
+            if world.get(x, y) {
                 print!("#");
             } else {
                 print!(" ");
@@ -101,21 +98,20 @@ pub fn print_world(world: &World) {
 
 pub fn a() {
     let input = read_input();
-    let world = input.points.into_iter().collect();
+    let mut world = to_world(input.points, &input.folds);
 
     let (axis, value) = input.folds[0];
-    let new_world = fold_points(world, axis, value);
-    println!("Day13a: {}", new_world.len());
+    fold_world(&mut world, axis, value);
+    println!("Day13a: {}", world.count_ones());
 }
 
 pub fn b() {
     let input = read_input();
-    let world = input.points.into_iter().collect();
+    let mut world = to_world(input.points, &input.folds);
 
-    let world = input.folds.into_iter().fold(world, |world, (axis, value)| {
-        let new_world = fold_points(world, axis, value);
-        new_world
-    });
+    for (axis, value) in input.folds {
+        fold_world(&mut world, axis, value);
+    }
 
     println!("Day13b:");
     print_world(&world);
@@ -1,5 +1,7 @@
 use itertools::Itertools;
 
+use crate::util::IterUtil;
+
 const INPUT: &str = include_str!("./day14.txt");
 
 #[derive(Debug)]
@@ -57,8 +59,7 @@ fn solve(iterations: usize) -> usize {
     let (min, max) = element_counts
         .values()
         .copied()
-        .minmax()
-        .into_option()
+        .min_max_by_key(|&count| count)
         .unwrap();
 
     max - min
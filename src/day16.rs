@@ -43,11 +43,7 @@ impl Packet {
                     Operator::EqualTo => |a, b| (a == b) as Literal,
                 };
 
-                packets
-                    .iter()
-                    .map(|a| a.eval())
-                    .reduce(|a, b| op_fn(a, b))
-                    .unwrap()
+                packets.iter().map(|a| a.eval()).reduce(op_fn).unwrap()
             }
         }
     }
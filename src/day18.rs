@@ -8,6 +8,8 @@ use std::{
 
 use itertools::Itertools;
 
+use crate::util::{fixpoint, IterUtil};
+
 const INPUT: &str = include_str!("./day18.txt");
 
 trait ParseExt<T> {
@@ -49,6 +51,7 @@ impl Tree {
         }
     }
 
+    #[allow(dead_code)]
     fn as_pair(&self) -> Option<&Pair> {
         match self {
             Tree::Pair(pair) => Some(pair),
@@ -357,17 +360,11 @@ fn reduce_once_with_action(pair: Pair, only_action: Action) -> Tree {
     }
 }
 
-fn reduce_all(mut acc: Tree) -> Tree {
-    loop {
+fn reduce_all(acc: Tree) -> Tree {
+    fixpoint(acc, |acc| {
         let exploded = reduce_with_action(acc.clone().unwrap_pair(), Action::Explosion);
-        let split = reduce_once_with_action(exploded.unwrap_pair(), Action::Split);
-
-        if split == acc {
-            return split;
-        } else {
-            acc = split;
-        }
-    }
+        reduce_once_with_action(exploded.unwrap_pair(), Action::Split)
+    })
 }
 
 fn sum_and_reduce(mut trees: Vec<Tree>) -> Tree {
@@ -397,13 +394,12 @@ pub fn ab() {
 
     let mut largest_magnitude = usize::MIN;
 
-    for pair in input.into_iter().permutations(2) {
-        let a = pair[0].clone();
-        let b = pair[1].clone();
-        let sum = add_and_reduce(a, b);
-        let total_magnitude = magnitude(sum);
+    // Snailfish addition isn't commutative, so try both orders
+    for (a, b) in input.into_iter().pairwise_combinations() {
+        let ab = magnitude(add_and_reduce(a.clone(), b.clone()));
+        let ba = magnitude(add_and_reduce(b, a));
 
-        largest_magnitude = largest_magnitude.max(total_magnitude);
+        largest_magnitude = largest_magnitude.max(ab).max(ba);
     }
 
     println!("Day18b: {}", largest_magnitude);
@@ -418,7 +414,6 @@ mod test {
             ReductionResult::Unchanged(pair) => pair,
             ReductionResult::Reduced(pair, _) => pair,
             ReductionResult::Exploding { tree: pair, .. } => pair,
-            _ => unreachable!(),
         }
     }
 
@@ -1,59 +1,267 @@
+use anyhow::Context;
+
 const INPUT: &str = include_str!("./day2.txt");
 
-#[derive(Debug, Clone, Copy)]
-enum Direction {
-    Up,
-    Down,
-    Forward
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    pub horizontal: i32,
+    pub depth: i32,
+    pub aim: i32,
 }
 
-struct Command(Direction, i32);
+// The parser doesn't know which verbs exist, or which of them take command.required_units()?, that's up to the
+// movement model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub verb: String,
+    pub units: Option<i32>,
+}
 
-fn get_commands() -> Vec<Command> {
-  INPUT.lines().map(|line| {
-    let (direction, units) = line.split_once(' ').unwrap();
-    let direction = match direction {
-      "up" => Direction::Up,
-      "down" => Direction::Down,
-      "forward" => Direction::Forward,
-      _ => panic!(),
-    };
+impl Command {
+    /// The units of a verb that can't do without them.
+    pub fn required_units(&self) -> anyhow::Result<i32> {
+        self.units
+            .with_context(|| format!("{} needs a number of units", self.verb))
+    }
+}
 
-    let units = units.parse().unwrap();
+pub trait MovementModel {
+    fn apply(&self, position: Position, command: &Command) -> anyhow::Result<Position>;
+}
 
-    Command(direction, units)
-  }).collect()
+fn unknown_command(command: &Command) -> anyhow::Error {
+    anyhow::anyhow!("unknown command: {}", command.verb)
 }
 
-fn day2a() {
-  let commands = get_commands().into_iter().fold((0, 0), |(x, y), command| {
-    match command.0 {
-      Direction::Up => (x, y - command.1),
-      Direction::Down => (x, y + command.1),
-      Direction::Forward => (x + command.1, y),
+/// Part a: up and down change the depth directly.
+pub struct DirectModel;
+
+impl MovementModel for DirectModel {
+    fn apply(&self, p: Position, command: &Command) -> anyhow::Result<Position> {
+        Ok(match command.verb.as_str() {
+            "up" => Position {
+                depth: p.depth - command.required_units()?,
+                ..p
+            },
+            "down" => Position {
+                depth: p.depth + command.required_units()?,
+                ..p
+            },
+            "forward" => Position {
+                horizontal: p.horizontal + command.required_units()?,
+                ..p
+            },
+            _ => return Err(unknown_command(command)),
+        })
     }
-  });
+}
 
-  let (horizontal, vertical) = commands;
+/// Part b: up and down change the aim, and moving forward changes the depth by the aim.
+pub struct AimModel;
 
-  println!("Day2a: {}", horizontal * vertical);
+impl MovementModel for AimModel {
+    fn apply(&self, p: Position, command: &Command) -> anyhow::Result<Position> {
+        Ok(match command.verb.as_str() {
+            "up" => Position {
+                aim: p.aim - command.required_units()?,
+                ..p
+            },
+            "down" => Position {
+                aim: p.aim + command.required_units()?,
+                ..p
+            },
+            "forward" => {
+                let units = command.required_units()?;
+                Position {
+                    horizontal: p.horizontal + units,
+                    depth: p.depth + p.aim * units,
+                    ..p
+                }
+            }
+            _ => return Err(unknown_command(command)),
+        })
+    }
 }
 
-fn day2b() {
-  let commands = get_commands().into_iter().fold((0, 0, 0), |(x, y, aim), command| {
-    match command.0 {
-      Direction::Down => (x, y, aim + command.1),
-      Direction::Up => (x, y, aim - command.1),
-      Direction::Forward => (x + command.1, y + (aim * command.1), aim),
+pub struct Submarine<M: MovementModel> {
+    model: M,
+    trajectory: Vec<Position>,
+}
+
+impl<M: MovementModel> Submarine<M> {
+    pub fn new(model: M) -> Self {
+        Submarine {
+            model,
+            trajectory: vec![Position::default()],
+        }
     }
-  });
 
-  let (horizontal, vertical, _aim) = commands;
+    pub fn position(&self) -> Position {
+        *self.trajectory.last().unwrap()
+    }
 
-  println!("Day2b: {}", horizontal * vertical);
+    /// Every position the submarine has been in, starting from the origin.
+    pub fn trajectory(&self) -> &[Position] {
+        &self.trajectory
+    }
+
+    pub fn execute(&mut self, command: &Command) -> anyhow::Result<()> {
+        let next = self.model.apply(self.position(), command)?;
+        self.trajectory.push(next);
+        Ok(())
+    }
+
+    pub fn run<'a>(
+        &mut self,
+        commands: impl IntoIterator<Item = &'a Command>,
+    ) -> anyhow::Result<()> {
+        for (i, command) in commands.into_iter().enumerate() {
+            self.execute(command)
+                .with_context(|| format!("command {}", i + 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_commands(input: &str) -> anyhow::Result<Vec<Command>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let (verb, units) = match line.split_once(' ') {
+                Some((verb, units)) => (verb, Some(units)),
+                None => (line, None),
+            };
+            anyhow::ensure!(!verb.is_empty(), "line {}: missing command", i + 1);
+
+            let units = units
+                .map(|units| {
+                    units
+                        .parse()
+                        .with_context(|| format!("line {}: invalid units {:?}", i + 1, units))
+                })
+                .transpose()?;
+
+            Ok(Command {
+                verb: verb.to_string(),
+                units,
+            })
+        })
+        .collect()
+}
+
+fn navigate(model: impl MovementModel) -> i32 {
+    let commands = parse_commands(INPUT).unwrap();
+    let mut submarine = Submarine::new(model);
+    submarine.run(&commands).unwrap();
+
+    let position = submarine.position();
+    position.horizontal * position.depth
+}
+
+fn day2a() {
+    println!("Day2a: {}", navigate(DirectModel));
+}
+
+fn day2b() {
+    println!("Day2b: {}", navigate(AimModel));
 }
 
 pub fn day2() {
     day2a();
     day2b();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2";
+
+    fn final_position(model: impl MovementModel, input: &str) -> anyhow::Result<Position> {
+        let mut submarine = Submarine::new(model);
+        submarine.run(&parse_commands(input)?)?;
+        Ok(submarine.position())
+    }
+
+    // Handles "back" and "turn" on top of the aim model, without changes to the parser
+    struct ReversibleModel;
+
+    impl MovementModel for ReversibleModel {
+        fn apply(&self, p: Position, command: &Command) -> anyhow::Result<Position> {
+            match command.verb.as_str() {
+                "back" => {
+                    let units = command.required_units()?;
+                    Ok(Position {
+                        horizontal: p.horizontal - units,
+                        depth: p.depth - p.aim * units,
+                        ..p
+                    })
+                }
+                "turn" => Ok(Position { aim: -p.aim, ..p }),
+                _ => AimModel.apply(p, command),
+            }
+        }
+    }
+
+    #[test]
+    fn example_direct() {
+        let p = final_position(DirectModel, EXAMPLE).unwrap();
+        assert_eq!(p.horizontal * p.depth, 150);
+    }
+
+    #[test]
+    fn example_aim() {
+        let p = final_position(AimModel, EXAMPLE).unwrap();
+        assert_eq!(p.horizontal * p.depth, 900);
+    }
+
+    #[test]
+    fn trajectory_has_every_position() {
+        let mut submarine = Submarine::new(DirectModel);
+        submarine.run(&parse_commands(EXAMPLE).unwrap()).unwrap();
+
+        let trajectory = submarine.trajectory();
+        assert_eq!(trajectory.len(), 7);
+        assert_eq!(trajectory[0], Position::default());
+        assert_eq!(
+            trajectory[2],
+            Position {
+                horizontal: 5,
+                depth: 5,
+                aim: 0
+            }
+        );
+    }
+
+    #[test]
+    fn custom_commands() {
+        let p = final_position(ReversibleModel, "down 2\nforward 5\nturn\nback 3").unwrap();
+        assert_eq!(
+            p,
+            Position {
+                horizontal: 2,
+                depth: 16,
+                aim: -2
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_command_is_an_error() {
+        assert!(final_position(AimModel, "forward 1\nback 3").is_err());
+        assert!(final_position(AimModel, "forward x").is_err());
+        assert!(final_position(AimModel, "forward").is_err());
+        assert!(final_position(AimModel, "turn").is_err());
+        assert!(final_position(ReversibleModel, "back").is_err());
+    }
+
+    #[test]
+    fn units_are_optional() {
+        let commands = parse_commands("turn\nforward 3").unwrap();
+        assert_eq!(commands[0].units, None);
+        assert_eq!(commands[1].units, Some(3));
+        assert!(parse_commands("\n").is_err());
+    }
+}
@@ -1,108 +1,288 @@
-use itertools::Itertools;
+use anyhow::Context;
+
+use crate::util::IterUtil;
 
 const INPUT: &str = include_str!("./day3.txt");
-const BITS: usize = 12;
-
-fn read_input() -> Vec<([bool; BITS], &'static str)> {
-    INPUT
-        .lines()
-        .map(|line| {
-            (
-                line.chars()
-                    .map(|c| match c {
-                        '0' => false,
-                        '1' => true,
-                        _ => panic!(),
-                    })
-                    .collect_vec()
-                    .try_into()
-                    .unwrap(),
-                line,
-            )
-        })
-        .collect_vec()
+
+/// A diagnostic report with each row packed into an integer, most significant bit first.
+/// The bit width is taken from the input, and can be up to 128 bits.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    width: usize,
+    rows: Vec<u128>,
 }
 
-pub fn a() {
-    let input = read_input();
-    let mut bit_popularities = [(0, 0); BITS];
+impl Diagnostic {
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut width = None;
+        let mut rows = Vec::new();
 
-    for row in input {
-        for (i, bit) in row.0.into_iter().enumerate() {
-            if bit {
-                bit_popularities[i].1 += 1;
-            } else {
-                bit_popularities[i].0 += 1;
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+
+            match width {
+                None => {
+                    anyhow::ensure!(
+                        !line.is_empty() && line.len() <= u128::BITS as usize,
+                        "line {}: rows must have between 1 and {} bits",
+                        i + 1,
+                        u128::BITS
+                    );
+                    width = Some(line.len());
+                }
+                Some(width) => anyhow::ensure!(
+                    line.len() == width,
+                    "line {}: expected {} bits but got {}",
+                    i + 1,
+                    width,
+                    line.len()
+                ),
             }
+
+            anyhow::ensure!(
+                line.bytes().all(|b| b == b'0' || b == b'1'),
+                "line {}: invalid binary number {:?}",
+                i + 1,
+                line
+            );
+            rows.push(u128::from_str_radix(line, 2)?);
         }
+
+        Ok(Diagnostic {
+            width: width.context("empty diagnostic report")?,
+            rows,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    fn mask(&self) -> u128 {
+        u128::MAX >> (u128::BITS as usize - self.width)
+    }
+
+    /// Number of rows with the given bit set, counting from the least significant bit.
+    fn column_popcount(&self, bit: usize) -> usize {
+        self.rows.iter().count_where(|&&row| row >> bit & 1 == 1)
     }
 
-    let mut gamma = String::new();
-    let mut epsilon = String::new();
+    /// The most common value of each bit, with ties going to 1.
+    pub fn gamma(&self) -> u128 {
+        (0..self.width)
+            .filter(|&bit| 2 * self.column_popcount(bit) >= self.rows.len())
+            .fold(0, |gamma, bit| gamma | 1 << bit)
+    }
+
+    pub fn epsilon(&self) -> u128 {
+        !self.gamma() & self.mask()
+    }
+
+    /// Gamma times epsilon, or `None` if the product doesn't fit in a u128, which can only
+    /// happen for rows wider than 64 bits.
+    pub fn power_consumption(&self) -> Option<u128> {
+        self.gamma().checked_mul(self.epsilon())
+    }
 
-    for (zero, one) in bit_popularities {
-        if zero > one {
-            gamma.push('0');
-            epsilon.push('1');
+    pub fn rating_trie(&self) -> RatingTrie {
+        RatingTrie::new(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TrieNode {
+    // Index 0 is the root, so it doubles as "no child"
+    children: [u32; 2],
+    count: u32,
+}
+
+/// A binary prefix trie over the rows of a diagnostic, most significant bit first, where each
+/// node knows how many rows pass through it.
+pub struct RatingTrie {
+    width: usize,
+    nodes: Vec<TrieNode>,
+}
+
+impl RatingTrie {
+    fn new(diagnostic: &Diagnostic) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+
+        for &row in &diagnostic.rows {
+            let mut node = 0;
+            nodes[0].count += 1;
+
+            for bit in (0..diagnostic.width).rev() {
+                let side = (row >> bit & 1) as usize;
+
+                if nodes[node].children[side] == 0 {
+                    nodes.push(TrieNode::default());
+                    nodes[node].children[side] = (nodes.len() - 1) as u32;
+                }
+
+                node = nodes[node].children[side] as usize;
+                nodes[node].count += 1;
+            }
+        }
+
+        RatingTrie {
+            width: diagnostic.width,
+            nodes,
+        }
+    }
+
+    fn count(&self, node: u32) -> i32 {
+        if node == 0 {
+            0
         } else {
-            gamma.push('1');
-            epsilon.push('0');
+            self.nodes[node as usize].count as i32
         }
     }
 
-    let gamma = u32::from_str_radix(&gamma, 2).unwrap();
-    let epsilon = u32::from_str_radix(&epsilon, 2).unwrap();
+    /// Walks down the trie, letting `criteria(zeroes, ones)` pick the bit to keep while more than
+    /// one row remains. If the chosen side has no rows, the other side is taken instead.
+    pub fn find_rating(&self, criteria: fn(i32, i32) -> bool) -> u128 {
+        let mut node = 0;
+        let mut rating = 0;
 
-    println!("Day 3a: {}", gamma * epsilon);
-}
+        for _ in 0..self.width {
+            let [zero, one] = self.nodes[node].children;
+            let (zeroes, ones) = (self.count(zero), self.count(one));
+
+            let side = if zeroes == 0 {
+                1
+            } else if ones == 0 {
+                0
+            } else {
+                criteria(zeroes, ones) as usize
+            };
 
-fn find_rating(
-    mut input: Vec<([bool; BITS], &'static str)>,
-    criteria: fn(i32, i32) -> bool,
-) -> u32 {
-    let mut i = 0;
-
-    while input.len() > 1 {
-        let mut zeroes = 0;
-        let mut ones = 0;
-
-        for (bits, _) in &input {
-          if bits[i] {
-            ones += 1;
-          } else {
-            zeroes += 1;
-          }
+            node = self.nodes[node].children[side] as usize;
+            rating = rating << 1 | side as u128;
         }
 
-        let winner = criteria(zeroes, ones);
-        input.retain(|(bits, _)| bits[i] == winner);
-        i += 1;
+        rating
+    }
+}
+
+fn o2_criteria(zero: i32, one: i32) -> bool {
+    if zero == one {
+        true
+    } else {
+        one > zero
+    }
+}
+
+fn co2_criteria(zero: i32, one: i32) -> bool {
+    if zero == one {
+        false
+    } else {
+        one < zero
     }
+}
 
-    let rating = u32::from_str_radix(&input[0].1, 2).unwrap();
-    rating
+pub fn a() {
+    let input = Diagnostic::parse(INPUT).unwrap();
+    let power = input
+        .power_consumption()
+        .expect("power consumption overflows");
+    println!("Day 3a: {}", power);
 }
 
 pub fn b() {
-    let input = read_input();
+    let trie = Diagnostic::parse(INPUT).unwrap().rating_trie();
 
-    let o2 = find_rating(input.clone(), |zero, one| {
-        if zero == one {
-            true
-        } else {
-            one > zero
-        }
-    });
-    let co2 = find_rating(input.clone(), |zero, one| {
-        if zero == one {
-            false
-        } else {
-            one < zero
-        }
-    });
+    let o2 = trie.find_rating(o2_criteria);
+    let co2 = trie.find_rating(co2_criteria);
 
     println!("O2: {}", o2);
     println!("CO2: {}", co2);
 
-    println!("Day 3b: {}", o2 * co2);
+    let life_support = o2.checked_mul(co2).expect("life support rating overflows");
+    println!("Day 3b: {}", life_support);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "00100\n11110\n10110\n10111\n10101\n01111\n\
+                           00111\n11100\n10000\n11001\n00010\n01010\n";
+
+    #[test]
+    fn example_power_consumption() {
+        let input = Diagnostic::parse(EXAMPLE).unwrap();
+        assert_eq!(input.width(), 5);
+        assert_eq!(input.gamma(), 22);
+        assert_eq!(input.epsilon(), 9);
+        assert_eq!(input.power_consumption(), Some(198));
+    }
+
+    // The straightforward version, filtering the whole list once per bit
+    fn find_rating_by_filtering(input: &Diagnostic, criteria: fn(i32, i32) -> bool) -> u128 {
+        let mut rows = input.rows.clone();
+        let mut bit = input.width;
+
+        while rows.len() > 1 {
+            bit -= 1;
+
+            let ones = rows.iter().count_where(|&&row| row >> bit & 1 == 1) as i32;
+            let zeroes = rows.len() as i32 - ones;
+
+            let winner = criteria(zeroes, ones) as u128;
+            rows.retain(|&row| row >> bit & 1 == winner);
+        }
+
+        rows[0]
+    }
+
+    #[test]
+    fn example_life_support() {
+        let trie = Diagnostic::parse(EXAMPLE).unwrap().rating_trie();
+        assert_eq!(trie.find_rating(o2_criteria), 23);
+        assert_eq!(trie.find_rating(co2_criteria), 10);
+    }
+
+    #[test]
+    fn trie_matches_filtering() {
+        let input = Diagnostic::parse(INPUT).unwrap();
+        let trie = input.rating_trie();
+
+        for criteria in [o2_criteria, co2_criteria] {
+            assert_eq!(
+                trie.find_rating(criteria),
+                find_rating_by_filtering(&input, criteria)
+            );
+        }
+    }
+
+    #[test]
+    fn custom_criteria() {
+        let trie = Diagnostic::parse(EXAMPLE).unwrap().rating_trie();
+        assert_eq!(trie.find_rating(|_, _| false), 0b00010);
+        assert_eq!(trie.find_rating(|_, _| true), 0b11110);
+    }
+
+    #[test]
+    fn inconsistent_widths_are_rejected() {
+        assert!(Diagnostic::parse("0101\n011\n").is_err());
+        assert!(Diagnostic::parse("0102\n").is_err());
+        assert!(Diagnostic::parse("").is_err());
+        assert!(Diagnostic::parse(&"1".repeat(129)).is_err());
+    }
+
+    #[test]
+    fn rows_up_to_128_bits() {
+        let high = format!("1{}", "0".repeat(127));
+        let low = format!("{}1", "0".repeat(127));
+        let input = Diagnostic::parse(&format!("{}\n{}\n{}\n", high, high, low)).unwrap();
+
+        assert_eq!(input.width(), 128);
+        assert_eq!(input.gamma(), 1 << 127);
+        assert_eq!(input.epsilon(), !(1 << 127));
+        assert_eq!(input.power_consumption(), None);
+
+        let trie = input.rating_trie();
+        assert_eq!(trie.find_rating(o2_criteria), 1 << 127);
+        assert_eq!(trie.find_rating(co2_criteria), 1);
+    }
 }
@@ -1,131 +1,453 @@
-use itertools::Itertools;
-use retain_mut::RetainMut;
+use std::collections::HashMap;
 
-use crate::util::BoolIterUtil;
+use anyhow::Context;
+use bitflags::bitflags;
+use itertools::Itertools;
 
 const INPUT: &str = include_str!("./day4.txt");
 
-type Board = [[(u32, bool); 5]; 5];
+bitflags! {
+  pub struct WinPatterns: u8 {
+    const ROWS = 0b0000_0001;
+    const COLUMNS = 0b0000_0010;
+    // Only allowed on square boards
+    const DIAGONALS = 0b0000_0100;
+    const FOUR_CORNERS = 0b0000_1000;
+    const FULL_CARD = 0b0001_0000;
 
-fn parse_input() -> (Vec<u32>, Vec<Board>) {
-    let mut lines = INPUT.lines();
-
-    let numbers: Vec<u32> = lines
-        .next()
-        .unwrap()
-        .split(',')
-        .map(|s| s.parse().unwrap())
-        .collect();
-
-    let boards = lines
-        .filter(|&line| line != "")
-        .chunks(5)
-        .into_iter()
-        .map(|chunk| {
-            let board: Board = chunk
-                .map(|line| {
-                    line.split_ascii_whitespace()
-                        .map(|number| (number.parse().unwrap(), false))
-                        .collect_vec()
-                        .try_into()
-                        .unwrap()
-                })
-                .collect_vec()
-                .try_into()
-                .unwrap();
-            board
-        })
-        .collect_vec();
+    const STANDARD = Self::ROWS.bits | Self::COLUMNS.bits;
+  }
+}
 
-    (numbers, boards)
+/// The winning lines of a board size, as lists of cell indices.
+struct Layout {
+    width: usize,
+    height: usize,
+    lines: Vec<Vec<usize>>,
+    lines_by_cell: Vec<Vec<usize>>,
 }
 
-fn mark_number(number: u32, board: &mut Board) {
-    for row in board {
-        for (n, marked) in row {
-            if *n == number {
-                *marked = true;
-                return;
+impl Layout {
+    fn new(width: usize, height: usize, patterns: WinPatterns) -> Self {
+        let mut lines: Vec<Vec<usize>> = Vec::new();
+
+        if patterns.contains(WinPatterns::ROWS) {
+            lines.extend((0..height).map(|y| (0..width).map(|x| y * width + x).collect()));
+        }
+
+        if patterns.contains(WinPatterns::COLUMNS) {
+            lines.extend((0..width).map(|x| (0..height).map(|y| y * width + x).collect()));
+        }
+
+        if patterns.contains(WinPatterns::DIAGONALS) {
+            lines.push((0..width).map(|i| i * width + i).collect());
+            lines.push((0..width).map(|i| i * width + (width - 1 - i)).collect());
+        }
+
+        if patterns.contains(WinPatterns::FOUR_CORNERS) {
+            let corners = [0, width - 1, (height - 1) * width, height * width - 1];
+            lines.push(corners.into_iter().unique().collect());
+        }
+
+        if patterns.contains(WinPatterns::FULL_CARD) {
+            lines.push((0..width * height).collect());
+        }
+
+        let mut lines_by_cell = vec![Vec::new(); width * height];
+        for (line, cells) in lines.iter().enumerate() {
+            for &cell in cells {
+                lines_by_cell[cell].push(line);
             }
         }
+
+        Layout {
+            width,
+            height,
+            lines,
+            lines_by_cell,
+        }
     }
 }
 
-fn check_bingo(board: &Board) -> bool {
-    let any_row = board
-        .iter()
-        .map(|row| row.iter().map(|(_, marked)| marked).all_true())
-        .any(|id| id);
+pub struct Board {
+    numbers: Vec<u32>,
+    marked: Vec<bool>,
+    cells_by_number: HashMap<u32, usize>,
+    marks_per_line: Vec<usize>,
+    won: bool,
+}
+
+impl Board {
+    fn new(numbers: Vec<u32>, layout: &Layout) -> anyhow::Result<Self> {
+        let mut cells_by_number = HashMap::new();
+        for (cell, &number) in numbers.iter().enumerate() {
+            anyhow::ensure!(
+                cells_by_number.insert(number, cell).is_none(),
+                "{} appears more than once",
+                number
+            );
+        }
+
+        Ok(Board {
+            marked: vec![false; numbers.len()],
+            numbers,
+            cells_by_number,
+            marks_per_line: vec![0; layout.lines.len()],
+            won: false,
+        })
+    }
+
+    /// Marks the number and returns true if that completed any winning line.
+    fn mark(&mut self, number: u32, layout: &Layout) -> bool {
+        let cell = match self.cells_by_number.get(&number) {
+            Some(&cell) if !self.marked[cell] => cell,
+            _ => return false,
+        };
+
+        self.marked[cell] = true;
 
-    if any_row {
-        return true;
+        let mut completed = false;
+        for &line in &layout.lines_by_cell[cell] {
+            self.marks_per_line[line] += 1;
+            completed |= self.marks_per_line[line] == layout.lines[line].len();
+        }
+
+        completed
+    }
+
+    pub fn has_won(&self) -> bool {
+        self.won
     }
 
-    (0..5)
-        .map(|col| (0..5).map(|row| board[row][col].1).all_true())
-        .any_true()
+    pub fn unmarked_sum(&self) -> u32 {
+        self.numbers
+            .iter()
+            .zip(&self.marked)
+            .filter(|(_, &marked)| !marked)
+            .map(|(n, _)| n)
+            .sum()
+    }
 }
 
-fn unmarked_numbers(board: &Board) -> Vec<u32> {
-    let mut numbers = vec![];
-    for row in board {
-        for (n, marked) in row {
-            if !*marked {
-                numbers.push(*n);
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinEvent {
+    pub draw_index: usize,
+    pub number: u32,
+    pub board_id: usize,
+    pub score: u32,
+}
+
+/// Every win of a finished game, in the order they happened. Boards that win on the same draw
+/// are ordered by id.
+pub struct Timeline {
+    events: Vec<WinEvent>,
+    board_count: usize,
+}
+
+impl Timeline {
+    pub fn events(&self) -> &[WinEvent] {
+        &self.events
+    }
+
+    pub fn first_winner(&self) -> Option<&WinEvent> {
+        self.events.first()
+    }
+
+    pub fn last_winner(&self) -> Option<&WinEvent> {
+        self.events.last()
+    }
+
+    /// The k-th board to win, counting from 0.
+    pub fn kth_winner(&self, k: usize) -> Option<&WinEvent> {
+        self.events.get(k)
+    }
+
+    pub fn never_won(&self) -> Vec<usize> {
+        let mut won = vec![false; self.board_count];
+        for event in &self.events {
+            won[event.board_id] = true;
         }
+
+        (0..self.board_count).filter(|&id| !won[id]).collect()
     }
-    numbers
 }
 
-pub fn a() {
-    let (numbers, mut boards) = parse_input();
-
-    for number in numbers {
-        for board in boards.iter_mut() {
-            mark_number(number, board);
-            if check_bingo(board) {
-                let sum_of_unmarked: u32 = unmarked_numbers(board).into_iter().sum();
-                println!("Day4a: {}", sum_of_unmarked * number);
-                return;
+pub struct Bingo {
+    layout: Layout,
+    boards: Vec<Board>,
+}
+
+impl Bingo {
+    /// Each board is a list of `width * height` distinct numbers in row-major order.
+    pub fn new(
+        width: usize,
+        height: usize,
+        boards: Vec<Vec<u32>>,
+        patterns: WinPatterns,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(width > 0 && height > 0, "boards can't be empty");
+        anyhow::ensure!(
+            !patterns.contains(WinPatterns::DIAGONALS) || width == height,
+            "diagonals need a square board, not {}x{}",
+            width,
+            height
+        );
+
+        let layout = Layout::new(width, height, patterns);
+        let boards = boards
+            .into_iter()
+            .enumerate()
+            .map(|(id, numbers)| {
+                anyhow::ensure!(
+                    numbers.len() == width * height,
+                    "board {} has {} numbers, expected {}",
+                    id,
+                    numbers.len(),
+                    width * height
+                );
+                Board::new(numbers, &layout).with_context(|| format!("board {}", id))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Bingo { layout, boards })
+    }
+
+    pub fn board_size(&self) -> (usize, usize) {
+        (self.layout.width, self.layout.height)
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards
+    }
+
+    /// Marks the number on every board that hasn't won yet, and returns the boards that won
+    /// because of it.
+    pub fn draw(&mut self, number: u32) -> Vec<usize> {
+        let mut winners = Vec::new();
+
+        for (id, board) in self.boards.iter_mut().enumerate() {
+            if !board.won && board.mark(number, &self.layout) {
+                board.won = true;
+                winners.push(id);
             }
         }
+
+        winners
     }
 
-    println!("No solution found :(");
+    /// Draws the numbers in order, yielding each board's win as it happens.
+    pub fn play<'a>(&'a mut self, numbers: &'a [u32]) -> impl Iterator<Item = WinEvent> + 'a {
+        numbers
+            .iter()
+            .enumerate()
+            .flat_map(move |(draw_index, &number)| {
+                self.draw(number)
+                    .into_iter()
+                    .map(|board_id| WinEvent {
+                        draw_index,
+                        number,
+                        board_id,
+                        score: self.boards[board_id].unmarked_sum() * number,
+                    })
+                    .collect_vec()
+            })
+    }
+
+    pub fn timeline(mut self, numbers: &[u32]) -> Timeline {
+        let events = self.play(numbers).collect();
+
+        Timeline {
+            events,
+            board_count: self.boards.len(),
+        }
+    }
 }
 
-pub fn b() {
-    let (numbers, mut boards) = parse_input();
-
-    while boards.len() > 0 {
-        for number in &numbers {
-            let boards_len = boards.len();
-            let mut found_winner = false;
-
-            boards.retain_mut(|board| {
-                mark_number(*number, board);
-                let found_bingo = check_bingo(board);
-
-                if found_bingo {
-                    if boards_len > 1 {
-                        false
-                    } else {
-                        found_winner = true;
-                        true
-                    }
-                } else {
-                    true
-                }
-            });
-
-            if boards.len() == 1 && found_winner {
-                let sum_of_unmarked: u32 = unmarked_numbers(&boards[0]).into_iter().sum();
-                println!("Day4b: {}", sum_of_unmarked * number);
-                return;
-            }
+fn parse_input(input: &str, patterns: WinPatterns) -> anyhow::Result<(Vec<u32>, Bingo)> {
+    let mut lines = input.lines();
+
+    let numbers = lines
+        .next()
+        .context("missing drawn numbers")?
+        .split(',')
+        .map(|s| s.trim().parse().context("invalid drawn number"))
+        .collect::<anyhow::Result<Vec<u32>>>()?;
+
+    let mut size = None;
+    let mut boards = Vec::new();
+
+    for (is_board, group) in &lines.group_by(|line| !line.trim().is_empty()) {
+        if !is_board {
+            continue;
         }
+
+        let rows = group
+            .map(|line| {
+                line.split_ascii_whitespace()
+                    .map(|n| n.parse().with_context(|| format!("invalid number {:?}", n)))
+                    .collect::<anyhow::Result<Vec<u32>>>()
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let board_size = (rows[0].len(), rows.len());
+        anyhow::ensure!(
+            rows.iter().all(|row| row.len() == board_size.0),
+            "board {} has rows of different lengths",
+            boards.len()
+        );
+        let first_size = *size.get_or_insert(board_size);
+        anyhow::ensure!(
+            first_size == board_size,
+            "board {} is {}x{} but the first board is {}x{}",
+            boards.len(),
+            board_size.0,
+            board_size.1,
+            first_size.0,
+            first_size.1
+        );
+
+        boards.push(rows.into_iter().flatten().collect());
+    }
+
+    let (width, height) = size.context("no boards")?;
+
+    Ok((numbers, Bingo::new(width, height, boards, patterns)?))
+}
+
+fn timeline() -> Timeline {
+    let (numbers, bingo) = parse_input(INPUT, WinPatterns::STANDARD).unwrap();
+    bingo.timeline(&numbers)
+}
+
+pub fn a() {
+    match timeline().first_winner() {
+        Some(winner) => println!("Day4a: {}", winner.score),
+        None => println!("No solution found :("),
     }
+}
 
-    println!("No solution found :(");
+pub fn b() {
+    match timeline().last_winner() {
+        Some(winner) => println!("Day4b: {}", winner.score),
+        None => println!("No solution found :("),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7
+";
+
+    // Returns (number, board) for each win in order
+    fn play(bingo: &mut Bingo, numbers: &[u32]) -> Vec<(u32, usize)> {
+        bingo
+            .play(numbers)
+            .map(|event| (event.number, event.board_id))
+            .collect()
+    }
+
+    #[test]
+    fn example_timeline() {
+        let (numbers, bingo) = parse_input(EXAMPLE, WinPatterns::STANDARD).unwrap();
+        assert_eq!(bingo.board_size(), (5, 5));
+
+        let timeline = bingo.timeline(&numbers);
+        assert_eq!(
+            timeline.events(),
+            &[
+                WinEvent {
+                    draw_index: 11,
+                    number: 24,
+                    board_id: 2,
+                    score: 4512
+                },
+                WinEvent {
+                    draw_index: 13,
+                    number: 16,
+                    board_id: 0,
+                    score: 2192
+                },
+                WinEvent {
+                    draw_index: 14,
+                    number: 13,
+                    board_id: 1,
+                    score: 1924
+                },
+            ]
+        );
+        assert_eq!(timeline.first_winner().unwrap().score, 4512);
+        assert_eq!(timeline.last_winner().unwrap().score, 1924);
+        assert_eq!(timeline.kth_winner(1).unwrap().board_id, 0);
+        assert_eq!(timeline.kth_winner(3), None);
+        assert!(timeline.never_won().is_empty());
+    }
+
+    #[test]
+    fn boards_that_never_win() {
+        let (numbers, bingo) = parse_input(EXAMPLE, WinPatterns::STANDARD).unwrap();
+        let timeline = bingo.timeline(&numbers[..13]);
+        assert_eq!(timeline.events().len(), 1);
+        assert_eq!(timeline.never_won(), vec![0, 1]);
+    }
+
+    #[test]
+    fn diagonals() {
+        let mut bingo = Bingo::new(3, 3, vec![(1..=9).collect()], WinPatterns::DIAGONALS).unwrap();
+        assert!(bingo.draw(3).is_empty());
+        assert!(bingo.draw(5).is_empty());
+        assert_eq!(bingo.draw(7), vec![0]);
+    }
+
+    #[test]
+    fn four_corners_on_rectangular_board() {
+        let mut bingo =
+            Bingo::new(4, 2, vec![(1..=8).collect()], WinPatterns::FOUR_CORNERS).unwrap();
+        assert_eq!(play(&mut bingo, &[2, 3, 6, 7, 1, 4, 5]), vec![]);
+        assert_eq!(bingo.draw(8), vec![0]);
+    }
+
+    #[test]
+    fn full_card() {
+        let mut bingo = Bingo::new(2, 2, vec![vec![1, 2, 3, 4]], WinPatterns::FULL_CARD).unwrap();
+        assert_eq!(play(&mut bingo, &[1, 2, 3, 9, 4]), vec![(4, 0)]);
+        assert_eq!(bingo.boards()[0].unmarked_sum(), 0);
+    }
+
+    #[test]
+    fn mismatched_boards_are_rejected() {
+        assert!(parse_input("1,2\n\n1 2\n3 4\n\n1 2 3\n4 5 6\n", WinPatterns::STANDARD).is_err());
+        assert!(parse_input("1,2\n\n1 2\n3\n", WinPatterns::STANDARD).is_err());
+    }
+
+    #[test]
+    fn diagonals_need_a_square_board() {
+        assert!(Bingo::new(4, 2, vec![(1..=8).collect()], WinPatterns::DIAGONALS).is_err());
+        assert!(Bingo::new(4, 2, vec![(1..=8).collect()], WinPatterns::STANDARD).is_ok());
+    }
+
+    #[test]
+    fn duplicate_numbers_are_rejected() {
+        assert!(Bingo::new(2, 2, vec![vec![1, 2, 3, 1]], WinPatterns::STANDARD).is_err());
+        assert!(parse_input("1,2\n\n1 2\n2 3\n", WinPatterns::STANDARD).is_err());
+    }
 }
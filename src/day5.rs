@@ -1,12 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 const INPUT: &str = include_str!("./day5.txt");
 
 type Point = (i32, i32);
 type Line = (Point, Point);
 
-fn parse_input() -> Vec<Line> {
-    INPUT
+fn parse_input(input: &str) -> Vec<Line> {
+    input
         .lines()
         .map(|line| {
             let (a, b) = line.split_once(" -> ").unwrap();
@@ -20,46 +20,62 @@ fn parse_input() -> Vec<Line> {
         .collect()
 }
 
-fn mark_covered_points(counts: &mut HashMap<Point, u32>, line: &Line, include_diagonal: bool) {
-    if line.0 .0 == line.1 .0 {
-        let start = line.0 .1.min(line.1 .1);
-        let end = line.0 .1.max(line.1 .1);
+/// Which lines are considered. Each filter includes the lines of the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineFilter {
+    AxisAligned,
+    Diagonal45,
+    All,
+}
 
-        for y in start..=end {
-            let coord = (line.0 .0, y);
-            *counts.entry(coord).or_insert(0) += 1;
-        }
-    } else if line.0 .1 == line.1 .1 {
-        let start = line.0 .0.min(line.1 .0);
-        let end = line.0 .0.max(line.1 .0);
+impl LineFilter {
+    fn accepts(self, &((x0, y0), (x1, y1)): &Line) -> bool {
+        let (dx, dy) = ((x1 - x0).abs(), (y1 - y0).abs());
 
-        for x in start..=end {
-            let coord = (x, line.0 .1);
-            *counts.entry(coord).or_insert(0) += 1;
-        }
-    } else if include_diagonal {
-        let start = line.0 .0.min(line.1 .0);
-        let end = line.0 .0.max(line.1 .0);
-
-        for x in start..=end {
-            let y = (line.0 .1 as f32
-                + (x as f32 - line.0 .0 as f32) * (line.1 .1 as f32 - line.0 .1 as f32)
-                    / (line.1 .0 as f32 - line.0 .0 as f32))
-                .round() as i32;
-            let coord = (x, y);
-            *counts.entry(coord).or_insert(0) += 1;
+        match self {
+            LineFilter::AxisAligned => dx == 0 || dy == 0,
+            LineFilter::Diagonal45 => dx == 0 || dy == 0 || dx == dy,
+            LineFilter::All => true,
         }
     }
 }
 
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Steps from one end to the other by (dx, dy) / gcd(dx, dy), which visits exactly the
+// integer points on the line at any slope
+fn line_points(&((x0, y0), (x1, y1)): &Line) -> impl Iterator<Item = Point> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let steps = gcd(dx.abs(), dy.abs());
+    let (step_x, step_y) = if steps == 0 {
+        (0, 0)
+    } else {
+        (dx / steps, dy / steps)
+    };
+
+    (0..=steps).map(move |i| (x0 + i * step_x, y0 + i * step_y))
+}
+
+fn mark_covered_points(counts: &mut HashMap<Point, u32>, line: &Line) {
+    for point in line_points(line) {
+        *counts.entry(point).or_insert(0) += 1;
+    }
+}
+
 // Prints a 2D grid representation of the board
 // Unmarked cells are marked with a '.'
 // Marked cells use the correspoding number
 fn print_board(counts: &HashMap<Point, u32>) {
-    let mut min_x = std::i32::MAX;
-    let mut max_x = std::i32::MIN;
-    let mut min_y = std::i32::MAX;
-    let mut max_y = std::i32::MIN;
+    let mut min_x = i32::MAX;
+    let mut max_x = i32::MIN;
+    let mut min_y = i32::MAX;
+    let mut max_y = i32::MIN;
 
     for (coord, _) in counts.iter() {
         min_x = coord.0.min(min_x);
@@ -83,21 +99,333 @@ fn print_board(counts: &HashMap<Point, u32>) {
     }
 }
 
-fn count_overlapping_points(include_diagonal: bool) -> usize {
-    let lines = parse_input();
+// Enumerates every covered point, kept as a reference for the analytic version
+fn count_overlapping_points_rasterised(lines: &[Line], filter: LineFilter, k: u32) -> usize {
     let mut counts = HashMap::new();
 
-    for line in lines.iter() {
-        mark_covered_points(&mut counts, line, include_diagonal);
+    for line in lines.iter().filter(|line| filter.accepts(line)) {
+        mark_covered_points(&mut counts, line);
     }
 
-    counts.into_values().filter(|&value| value >= 2).count()
+    counts.into_values().filter(|&value| value >= k).count()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Family {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl Family {
+    const ALL: [Family; 4] = [
+        Family::Horizontal,
+        Family::Vertical,
+        Family::Diagonal,
+        Family::AntiDiagonal,
+    ];
+
+    // (a, b) such that every line of the family is a * x + b * y = key
+    fn coefficients(self) -> (i64, i64) {
+        match self {
+            Family::Horizontal => (0, 1),
+            Family::Vertical => (1, 0),
+            Family::Diagonal => (1, -1),
+            Family::AntiDiagonal => (1, 1),
+        }
+    }
+
+    fn key(self, (x, y): (i64, i64)) -> i64 {
+        let (a, b) = self.coefficients();
+        a * x + b * y
+    }
+
+    // Where a point is along a line of this family
+    fn position(self, (x, y): (i64, i64)) -> i64 {
+        match self {
+            Family::Vertical => y,
+            _ => x,
+        }
+    }
+}
+
+/// A horizontal, vertical or 45° segment, as a range of positions on its carrier line.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    family: Family,
+    key: i64,
+    start: i64,
+    end: i64,
+}
+
+impl Segment {
+    fn from_line(&((x0, y0), (x1, y1)): &Line) -> Option<Segment> {
+        let (p0, p1) = ((x0 as i64, y0 as i64), (x1 as i64, y1 as i64));
+        let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+
+        let family = if dy == 0 {
+            Family::Horizontal
+        } else if dx == 0 {
+            Family::Vertical
+        } else if dx == dy {
+            Family::Diagonal
+        } else if dx == -dy {
+            Family::AntiDiagonal
+        } else {
+            return None;
+        };
+
+        let (t0, t1) = (family.position(p0), family.position(p1));
+
+        Some(Segment {
+            family,
+            key: family.key(p0),
+            start: t0.min(t1),
+            end: t0.max(t1),
+        })
+    }
+
+    /// The integer point where two segments of different families cross, if any.
+    fn intersection(&self, other: &Segment) -> Option<(i64, i64)> {
+        let (a1, b1) = self.family.coefficients();
+        let (a2, b2) = other.family.coefficients();
+        let det = a1 * b2 - a2 * b1;
+
+        if det == 0 {
+            return None;
+        }
+
+        let x = self.key * b2 - other.key * b1;
+        let y = a1 * other.key - a2 * self.key;
+
+        if x % det != 0 || y % det != 0 {
+            return None;
+        }
+
+        let point = (x / det, y / det);
+        (self.contains(point) && other.contains(point)).then_some(point)
+    }
+
+    fn contains(&self, point: (i64, i64)) -> bool {
+        (self.start..=self.end).contains(&self.family.position(point))
+    }
+}
+
+/// How many segments cover each position of one carrier line, as runs of
+/// (start, end exclusive, count). Positions outside the runs aren't covered.
+struct Coverage {
+    runs: Vec<(i64, i64, u32)>,
+}
+
+impl Coverage {
+    fn new<'a>(segments: impl Iterator<Item = &'a Segment>) -> Self {
+        let mut events = segments
+            .flat_map(|s| [(s.start, 1), (s.end + 1, -1)])
+            .collect::<Vec<(i64, i32)>>();
+        events.sort_unstable();
+
+        let mut runs = Vec::new();
+        let mut count = 0;
+
+        for (i, &(position, delta)) in events.iter().enumerate() {
+            count += delta;
+
+            if let Some(&(next, _)) = events.get(i + 1) {
+                if count > 0 && next > position {
+                    runs.push((position, next, count as u32));
+                }
+            }
+        }
+
+        Coverage { runs }
+    }
+
+    fn count_at(&self, position: i64) -> u32 {
+        let i = self.runs.partition_point(|&(_, end, _)| end <= position);
+
+        match self.runs.get(i) {
+            Some(&(start, _, count)) if start <= position => count,
+            _ => 0,
+        }
+    }
+
+    fn positions_with_count(&self, k: u32) -> i64 {
+        self.runs
+            .iter()
+            .filter(|&&(_, _, count)| count >= k)
+            .map(|&(start, end, _)| end - start)
+            .sum()
+    }
+}
+
+/// Counts the points covered by at least `k` lines without visiting every covered point.
+/// Overlaps along the same line are found with a 1D sweep, and the only other points that can
+/// reach `k` are where lines of different directions cross, which are found pairwise.
+/// Only works with horizontal, vertical and 45° lines.
+fn count_overlapping_points(lines: &[Line], filter: LineFilter, k: u32) -> anyhow::Result<usize> {
+    assert!(k > 0, "every point is covered at least 0 times");
+
+    let segments = lines
+        .iter()
+        .filter(|line| filter.accepts(line))
+        .map(|line| {
+            Segment::from_line(line)
+                .ok_or_else(|| anyhow::anyhow!("{:?} is not horizontal, vertical or 45°", line))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut carriers: HashMap<(Family, i64), Vec<Segment>> = HashMap::new();
+    for segment in &segments {
+        carriers
+            .entry((segment.family, segment.key))
+            .or_default()
+            .push(*segment);
+    }
+
+    let coverage: HashMap<(Family, i64), Coverage> = carriers
+        .into_iter()
+        .map(|(carrier, segments)| (carrier, Coverage::new(segments.iter())))
+        .collect();
+
+    let mut total: i64 = coverage.values().map(|c| c.positions_with_count(k)).sum();
+
+    let mut crossings = HashSet::new();
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            if let Some(point) = a.intersection(b) {
+                crossings.insert(point);
+            }
+        }
+    }
+
+    // A crossing was counted once for every carrier that reaches k on its own,
+    // but it should be counted once if all carriers through it reach k together
+    for point in crossings {
+        let counts = Family::ALL.map(|family| {
+            coverage
+                .get(&(family, family.key(point)))
+                .map_or(0, |c| c.count_at(family.position(point)))
+        });
+
+        total -= counts.iter().filter(|&&count| count >= k).count() as i64;
+
+        if counts.iter().sum::<u32>() >= k {
+            total += 1;
+        }
+    }
+
+    Ok(total as usize)
 }
 
 pub fn a() {
-    println!("Day5a: {}", count_overlapping_points(false));
+    let lines = parse_input(INPUT);
+    let count = count_overlapping_points(&lines, LineFilter::AxisAligned, 2).unwrap();
+    println!("Day5a: {}", count);
 }
 
 pub fn b() {
-    println!("Day5b: {}", count_overlapping_points(true));
+    let lines = parse_input(INPUT);
+    let count = count_overlapping_points(&lines, LineFilter::Diagonal45, 2).unwrap();
+    println!("Day5b: {}", count);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+
+    #[test]
+    fn example() {
+        let lines = parse_input(EXAMPLE);
+        assert_eq!(
+            count_overlapping_points(&lines, LineFilter::AxisAligned, 2).unwrap(),
+            5
+        );
+        assert_eq!(
+            count_overlapping_points(&lines, LineFilter::Diagonal45, 2).unwrap(),
+            12
+        );
+    }
+
+    #[test]
+    fn analytic_matches_rasterised() {
+        for input in [EXAMPLE, INPUT] {
+            let lines = parse_input(input);
+
+            for filter in [LineFilter::AxisAligned, LineFilter::Diagonal45] {
+                for k in 1..=3 {
+                    assert_eq!(
+                        count_overlapping_points(&lines, filter, k).unwrap(),
+                        count_overlapping_points_rasterised(&lines, filter, k),
+                        "{:?}, k = {}",
+                        filter,
+                        k
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crossings_off_the_integer_grid_dont_count() {
+        // These diagonals cross at (0.5, 0.5)
+        let lines = [((0, 0), (1, 1)), ((1, 0), (0, 1))];
+        assert_eq!(
+            count_overlapping_points(&lines, LineFilter::Diagonal45, 2).unwrap(),
+            0
+        );
+    }
+
+    // The two horizontal lines overlap on 1_999_991 points, and the vertical
+    // and the diagonal each cross the long one once
+    #[test]
+    fn huge_coordinates() {
+        let lines = [
+            ((0, 0), (1_000_000_000, 0)),
+            ((500_000_000, -5), (500_000_000, 5)),
+            ((-3, -3), (1_000_000_000, 1_000_000_000)),
+            ((10, 0), (2_000_000, 0)),
+        ];
+        assert_eq!(
+            count_overlapping_points(&lines, LineFilter::Diagonal45, 2).unwrap(),
+            1_999_991 + 2
+        );
+    }
+
+    #[test]
+    fn arbitrary_slopes_hit_only_integer_points() {
+        let points: Vec<_> = line_points(&((6, 3), (0, 0))).collect();
+        assert_eq!(points, vec![(6, 3), (4, 2), (2, 1), (0, 0)]);
+
+        let points: Vec<_> = line_points(&((1, 1), (4, 8))).collect();
+        assert_eq!(points, vec![(1, 1), (4, 8)]);
+
+        assert_eq!(line_points(&((3, 3), (3, 3))).count(), 1);
+    }
+
+    #[test]
+    fn filters() {
+        let steep = ((0, 0), (2, 4));
+        assert!(!LineFilter::Diagonal45.accepts(&steep));
+        assert!(LineFilter::All.accepts(&steep));
+        assert!(!LineFilter::AxisAligned.accepts(&((0, 0), (3, 3))));
+
+        let lines = [steep, ((0, 2), (2, 2))];
+        assert_eq!(
+            count_overlapping_points_rasterised(&lines, LineFilter::All, 2),
+            1
+        );
+        assert!(count_overlapping_points(&lines, LineFilter::All, 2).is_err());
+    }
 }
@@ -1,51 +1,349 @@
+use std::ops::{Add, Mul};
+
+use crate::bigint::BigUint;
+
 const INPUT: &str = include_str!("./day6.txt");
 
 fn read_input() -> Vec<u8> {
-    INPUT.split(',').map(|s| s.parse::<u8>().unwrap()).collect()
+    INPUT
+        .trim()
+        .split(',')
+        .map(|s| s.parse::<u8>().unwrap())
+        .collect()
 }
 
-pub fn a() {
-    let mut fish = read_input();
-    let mut new_fish = Vec::new();
-
-    for _day in 0..80 {
-        for f in fish.iter_mut() {
-            if *f == 0 {
-                *f = 6;
-                new_fish.push(8);
-            } else {
-                *f -= 1;
+/// A number type the population can be counted in.
+pub trait Count: Clone + Add<Output = Self> + Mul<Output = Self> {
+    fn from_u64(n: u64) -> Self;
+
+    fn zero() -> Self {
+        Self::from_u64(0)
+    }
+
+    fn one() -> Self {
+        Self::from_u64(1)
+    }
+}
+
+impl Count for u64 {
+    fn from_u64(n: u64) -> Self {
+        n
+    }
+}
+
+impl Count for u128 {
+    fn from_u64(n: u64) -> Self {
+        n as u128
+    }
+}
+
+impl Count for BigUint {
+    fn from_u64(n: u64) -> Self {
+        BigUint::from(n)
+    }
+}
+
+/// A u64 that becomes `None` once any calculation leading to it overflows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checked(pub Option<u64>);
+
+impl Add for Checked {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Checked(self.0.zip(rhs.0).and_then(|(a, b)| a.checked_add(b)))
+    }
+}
+
+impl Mul for Checked {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        // Phases without any fish multiply by zero, and whatever they would have grown into
+        // doesn't matter
+        match (self.0, rhs.0) {
+            (Some(0), _) | (_, Some(0)) => Checked(Some(0)),
+            (a, b) => Checked(a.zip(b).and_then(|(a, b)| a.checked_mul(b))),
+        }
+    }
+}
+
+impl Count for Checked {
+    fn from_u64(n: u64) -> Self {
+        Checked(Some(n))
+    }
+}
+
+/// Integers modulo `M`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modular<const M: u64>(pub u64);
+
+impl<const M: u64> Add for Modular<M> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Modular(((self.0 as u128 + rhs.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Mul for Modular<M> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Modular(((self.0 as u128 * rhs.0 as u128) % M as u128) as u64)
+    }
+}
+
+impl<const M: u64> Count for Modular<M> {
+    fn from_u64(n: u64) -> Self {
+        Modular(n % M)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Matrix<T> {
+    size: usize,
+    data: Vec<T>,
+}
+
+impl<T: Count> Matrix<T> {
+    fn zero(size: usize) -> Self {
+        Matrix {
+            size,
+            data: vec![T::zero(); size * size],
+        }
+    }
+
+    fn identity(size: usize) -> Self {
+        let mut matrix = Matrix::zero(size);
+        for i in 0..size {
+            matrix.data[i * size + i] = T::one();
+        }
+        matrix
+    }
+
+    fn get(&self, row: usize, col: usize) -> &T {
+        &self.data[row * self.size + col]
+    }
+
+    fn multiply(&self, other: &Matrix<T>) -> Matrix<T> {
+        let mut result = Matrix::zero(self.size);
+
+        for row in 0..self.size {
+            for col in 0..self.size {
+                result.data[row * self.size + col] = (0..self.size)
+                    .map(|i| self.get(row, i).clone() * other.get(i, col).clone())
+                    .fold(T::zero(), |acc, x| acc + x);
             }
         }
 
-        fish.append(&mut new_fish);
+        result
     }
 
-    println!("Day6a: {}", fish.len());
+    // Exponentiation by squaring
+    fn pow(&self, mut exponent: u64) -> Matrix<T> {
+        let mut result = Matrix::identity(self.size);
+        let mut base = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.multiply(&base);
+            }
+
+            exponent >>= 1;
+
+            if exponent > 0 {
+                base = base.multiply(&base);
+            }
+        }
+
+        result
+    }
+
+    fn apply(&self, vector: &[T]) -> Vec<T> {
+        (0..self.size)
+            .map(|row| {
+                vector
+                    .iter()
+                    .enumerate()
+                    .map(|(col, x)| self.get(row, col).clone() * x.clone())
+                    .fold(T::zero(), |acc, x| acc + x)
+            })
+            .collect()
+    }
 }
 
-pub fn b() {
-    let input = read_input();
-    let mut phases: [usize; 9] = [0; 9];
+/// How lanternfish age. Every day each timer goes down by one, and a fish whose timer is 0
+/// instead resets to `reset_age` and spawns a new fish with timer `newborn_age`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FishModel {
+    pub phases: usize,
+    pub reset_age: usize,
+    pub newborn_age: usize,
+}
 
-    for i in input {
-        phases[i as usize] += 1;
+impl Default for FishModel {
+    fn default() -> Self {
+        FishModel {
+            phases: 9,
+            reset_age: 6,
+            newborn_age: 8,
+        }
     }
+}
 
-    for _day in 0..256 {
-        let mut new_phases = [0; 9];
+impl FishModel {
+    fn transition<T: Count>(&self) -> Matrix<T> {
+        assert!(
+            self.reset_age < self.phases && self.newborn_age < self.phases,
+            "ages must be below the number of phases"
+        );
 
-        for phase in 1..phases.len() {
-            new_phases[phase - 1] += phases[phase];
+        let mut matrix = Matrix::zero(self.phases);
+
+        for phase in 1..self.phases {
+            matrix.data[(phase - 1) * self.phases + phase] = T::one();
         }
 
-        new_phases[8] += phases[0];
-        new_phases[6] += phases[0];
+        for age in [self.reset_age, self.newborn_age] {
+            let cell = &mut matrix.data[age * self.phases];
+            *cell = cell.clone() + T::one();
+        }
 
-        phases = new_phases;
+        matrix
     }
 
-    let total: usize = phases.iter().sum();
+    /// Number of fish after `days` days, in O(phases³ · log(days)) time.
+    pub fn population<T: Count>(&self, timers: &[u8], days: u64) -> T {
+        let mut counts = vec![0; self.phases];
+        for &timer in timers {
+            counts[timer as usize] += 1;
+        }
+        let counts: Vec<T> = counts.into_iter().map(T::from_u64).collect();
 
+        self.transition()
+            .pow(days)
+            .apply(&counts)
+            .into_iter()
+            .fold(T::zero(), |acc, x| acc + x)
+    }
+
+    pub fn checked_population(&self, timers: &[u8], days: u64) -> anyhow::Result<u64> {
+        let Checked(population) = self.population(timers, days);
+        population.ok_or_else(|| anyhow::anyhow!("population overflows u64 after {} days", days))
+    }
+}
+
+pub fn a() {
+    let total = FishModel::default()
+        .checked_population(&read_input(), 80)
+        .unwrap();
+    println!("Day6a: {}", total);
+}
+
+pub fn b() {
+    let total = FishModel::default()
+        .checked_population(&read_input(), 256)
+        .unwrap();
     println!("Day6b: {}", total);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: [u8; 5] = [3, 4, 3, 1, 2];
+
+    fn simulate(model: FishModel, timers: &[u8], days: usize) -> u64 {
+        let mut fish = timers.to_vec();
+
+        for _ in 0..days {
+            let mut newborns = Vec::new();
+
+            for f in fish.iter_mut() {
+                if *f == 0 {
+                    *f = model.reset_age as u8;
+                    newborns.push(model.newborn_age as u8);
+                } else {
+                    *f -= 1;
+                }
+            }
+
+            fish.append(&mut newborns);
+        }
+
+        fish.len() as u64
+    }
+
+    #[test]
+    fn example() {
+        let model = FishModel::default();
+        assert_eq!(model.population::<u64>(&EXAMPLE, 18), 26);
+        assert_eq!(model.population::<u64>(&EXAMPLE, 80), 5934);
+        assert_eq!(model.population::<u64>(&EXAMPLE, 256), 26984457539);
+    }
+
+    #[test]
+    fn custom_models_match_simulation() {
+        let models = [
+            FishModel {
+                phases: 4,
+                reset_age: 2,
+                newborn_age: 3,
+            },
+            FishModel {
+                phases: 5,
+                reset_age: 4,
+                newborn_age: 1,
+            },
+        ];
+
+        for model in models {
+            for days in [0, 1, 7, 20] {
+                assert_eq!(
+                    model.population::<u64>(&[0, 1, 3], days),
+                    simulate(model, &[0, 1, 3], days as usize)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let model = FishModel::default();
+        assert_eq!(
+            model.checked_population(&EXAMPLE, 256).unwrap(),
+            26984457539
+        );
+        assert!(model.checked_population(&EXAMPLE, 500).is_err());
+        assert!(model.checked_population(&EXAMPLE, u64::MAX).is_err());
+        assert_eq!(model.checked_population(&[], 1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn arbitrary_precision() {
+        let model = FishModel::default();
+
+        let big: BigUint = model.population(&EXAMPLE, 800);
+        let exact: u128 = model.population(&EXAMPLE, 800);
+        assert_eq!(big.to_string(), exact.to_string());
+
+        let huge: BigUint = model.population(&EXAMPLE, 10_000);
+        assert!(huge.bits() > 1000);
+    }
+
+    #[test]
+    fn huge_day_counts_modulo_prime() {
+        const P: u64 = 1_000_000_007;
+        let model = FishModel::default();
+
+        let exact: u64 = model.population(&EXAMPLE, 256);
+        let modular: Modular<P> = model.population(&EXAMPLE, 256);
+        assert_eq!(modular, Modular(exact % P));
+
+        assert_eq!(
+            model.population::<Modular<P>>(&EXAMPLE, 1_000_000_000_000_000),
+            Modular(3308113)
+        );
+    }
+}
@@ -1,54 +1,206 @@
 const INPUT: &str = include_str!("./day7.txt");
 
-fn read_input() -> Vec<i32> {
-    INPUT.split(',').map(|s| s.parse().unwrap()).collect()
+fn read_input() -> Vec<i64> {
+    INPUT
+        .trim()
+        .split(',')
+        .map(|s| s.parse().unwrap())
+        .collect()
 }
 
-pub fn a() {
-    let input = read_input();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: i64,
+    pub fuel: i64,
+}
+
+pub trait FuelCost {
+    /// Fuel for one crab to move `distance` steps.
+    fn cost(&self, distance: i64) -> i64;
+
+    fn total_fuel(&self, crabs: &[i64], position: i64) -> i64 {
+        crabs
+            .iter()
+            .map(|&crab| self.cost((crab - position).abs()))
+            .sum()
+    }
 
-    let min = *input.iter().min().unwrap();
-    let max = *input.iter().max().unwrap();
+    /// A position with the lowest total fuel. The default ternary search works for any convex
+    /// cost, since a sum of convex functions is convex. Panics if there are no crabs.
+    fn align(&self, crabs: &[i64]) -> Alignment {
+        ternary_search(crabs, |position| self.total_fuel(crabs, position))
+    }
+}
 
-    let mut smallest_diff = i32::MAX;
+fn ternary_search(crabs: &[i64], fuel: impl Fn(i64) -> i64) -> Alignment {
+    let mut lo = *crabs.iter().min().expect("no crabs");
+    let mut hi = *crabs.iter().max().unwrap();
 
-    for i in min..=max {
-        let mut diff = 0;
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
 
-        for &crab in &input {
-            diff += (i - crab).abs();
+        match fuel(m1).cmp(&fuel(m2)) {
+            std::cmp::Ordering::Less => hi = m2 - 1,
+            std::cmp::Ordering::Greater => lo = m1 + 1,
+            // A convex function is flat between them, or has its minimum between them
+            std::cmp::Ordering::Equal => {
+                lo = m1;
+                hi = m2;
+            }
         }
+    }
 
-        if diff < smallest_diff {
-            smallest_diff = diff;
+    best_of(lo..=hi, fuel)
+}
+
+fn best_of(positions: impl Iterator<Item = i64>, fuel: impl Fn(i64) -> i64) -> Alignment {
+    positions
+        .map(|position| Alignment {
+            position,
+            fuel: fuel(position),
+        })
+        .min_by_key(|alignment| alignment.fuel)
+        .expect("no positions")
+}
+
+/// Every step costs 1 fuel. The median minimizes the sum of distances.
+pub struct Linear;
+
+impl FuelCost for Linear {
+    fn cost(&self, distance: i64) -> i64 {
+        distance
+    }
+
+    fn align(&self, crabs: &[i64]) -> Alignment {
+        assert!(!crabs.is_empty(), "no crabs");
+
+        let mut sorted = crabs.to_vec();
+        sorted.sort_unstable();
+
+        let position = sorted[(sorted.len() - 1) / 2];
+
+        Alignment {
+            position,
+            fuel: self.total_fuel(crabs, position),
         }
     }
+}
+
+/// The n-th step costs n fuel. The total is (d² + d) / 2 summed over the crabs, whose minimum is
+/// always within half a step of the mean.
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: i64) -> i64 {
+        // sum of arithmetic series
+        distance * (1 + distance) / 2
+    }
 
-    println!("Day7a: {}", smallest_diff);
+    fn align(&self, crabs: &[i64]) -> Alignment {
+        assert!(!crabs.is_empty(), "no crabs");
+
+        let sum: i64 = crabs.iter().sum();
+        let n = crabs.len() as i64;
+        let floor_mean = sum.div_euclid(n);
+
+        best_of(floor_mean - 1..=floor_mean + 1, |position| {
+            self.total_fuel(crabs, position)
+        })
+    }
+}
+
+pub fn a() {
+    let input = read_input();
+    println!("Day7a: {}", Linear.align(&input).fuel);
 }
 
 pub fn b() {
     let input = read_input();
+    println!("Day7b: {}", Triangular.align(&input).fuel);
+}
 
-    let min = *input.iter().min().unwrap();
-    let max = *input.iter().max().unwrap();
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    let mut smallest_diff = i32::MAX;
+    const EXAMPLE: [i64; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
 
-    for i in min..=max {
-        let mut diff = 0;
+    struct Quadratic;
 
-        for &crab in &input {
-            // sum of arithmetic series
-            let n = (i - crab).abs();
-            let sum = n * (1 + n) / 2;
-            diff += sum;
+    impl FuelCost for Quadratic {
+        fn cost(&self, distance: i64) -> i64 {
+            distance * distance
         }
+    }
 
-        if diff < smallest_diff {
-            smallest_diff = diff;
+    fn brute_force(cost: &impl FuelCost, crabs: &[i64]) -> i64 {
+        let min = *crabs.iter().min().unwrap();
+        let max = *crabs.iter().max().unwrap();
+        (min..=max)
+            .map(|position| cost.total_fuel(crabs, position))
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn example() {
+        assert_eq!(
+            Linear.align(&EXAMPLE),
+            Alignment {
+                position: 2,
+                fuel: 37
+            }
+        );
+        assert_eq!(
+            Triangular.align(&EXAMPLE),
+            Alignment {
+                position: 5,
+                fuel: 168
+            }
+        );
+    }
+
+    #[test]
+    fn closed_forms_match_ternary_search() {
+        let input = read_input();
+
+        for crabs in [&EXAMPLE[..], &input[..]] {
+            let linear = ternary_search(crabs, |p| Linear.total_fuel(crabs, p));
+            let triangular = ternary_search(crabs, |p| Triangular.total_fuel(crabs, p));
+
+            assert_eq!(Linear.align(crabs).fuel, linear.fuel);
+            assert_eq!(Triangular.align(crabs).fuel, triangular.fuel);
+        }
+    }
+
+    #[test]
+    fn convex_costs_fall_back_to_ternary_search() {
+        for crabs in [&EXAMPLE[..], &[5], &[3, 3, 3], &[0, 1000], &[-7, 2, 40, 41]] {
+            assert_eq!(Quadratic.align(crabs).fuel, brute_force(&Quadratic, crabs));
+            assert_eq!(Linear.align(crabs).fuel, brute_force(&Linear, crabs));
+            assert_eq!(
+                Triangular.align(crabs).fuel,
+                brute_force(&Triangular, crabs)
+            );
         }
     }
 
-    println!("Day7b: {}", smallest_diff);
+    #[test]
+    #[should_panic(expected = "no crabs")]
+    fn linear_needs_crabs() {
+        Linear.align(&[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no crabs")]
+    fn triangular_needs_crabs() {
+        Triangular.align(&[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no crabs")]
+    fn ternary_search_needs_crabs() {
+        Quadratic.align(&[]);
+    }
 }
@@ -1,9 +1,10 @@
-use core::panic;
-use std::collections::{HashMap, HashSet};
+use std::{collections::BTreeSet, fmt::Display};
 
+use anyhow::Context;
 use bitflags::bitflags;
 use itertools::Itertools;
-use retain_mut::RetainMut;
+
+use crate::util::Rng;
 
 const INPUT: &str = include_str!("./day8.txt");
 
@@ -31,217 +32,732 @@ bitflags! {
   }
 }
 
-impl Segments {
-    fn from_char(c: char) -> Segments {
-        match c {
-            'a' => Segments::A,
-            'b' => Segments::B,
-            'c' => Segments::C,
-            'd' => Segments::D,
-            'e' => Segments::E,
-            'f' => Segments::F,
-            'g' => Segments::G,
-            _ => panic!("Invalid segment: {}", c),
-        }
-    }
-
-    fn count_bits(self) -> usize {
-        self.bits().count_ones() as usize
-    }
+/// Wires are written as letters, with `a` as wire 0 up to `z` as wire 25.
+fn group_to_segments(group: &str) -> anyhow::Result<u32> {
+    group.chars().try_fold(0, |wires, c| match c {
+        'a'..='z' => Ok(wires | 1 << (c as u32 - 'a' as u32)),
+        _ => anyhow::bail!("invalid wire: {}", c),
+    })
 }
 
-fn group_to_segments(group: &str) -> Segments {
-    group
-        .chars()
-        .map(Segments::from_char)
-        .fold(Segments::NONE, |acc, segment| acc | segment)
-}
+/// A line of input with the observed wire patterns on the left and the displayed ones on the
+/// right. Real captures don't always have all ten digits on the left.
+struct Line(String, Vec<u32>, Vec<u32>);
+
+fn parse_line(line: &str) -> anyhow::Result<Line> {
+    let (l, r) = line.split_once('|').context("missing '|'")?;
 
-struct Line(String, [Segments; 10], [Segments; 4]);
+    let parse_groups = |groups: &str| {
+        groups
+            .split_ascii_whitespace()
+            .map(group_to_segments)
+            .collect::<anyhow::Result<Vec<_>>>()
+    };
+
+    Ok(Line(line.to_string(), parse_groups(l)?, parse_groups(r)?))
+}
 
 fn read_input() -> Vec<Line> {
     INPUT
         .lines()
-        .map(|line| {
-            let (l, r) = line.split_once('|').unwrap();
-            let l = l.trim();
-            let r = r.trim();
-
-            let input_segment_groups: [Segments; 10] = l
-                .split(' ')
-                .map(group_to_segments)
-                .collect_vec()
-                .try_into()
-                .unwrap();
-            let output_segment_groups: [Segments; 4] = r
-                .split(' ')
-                .map(group_to_segments)
-                .collect_vec()
-                .try_into()
-                .unwrap();
-
-            Line(
-                line.to_string(),
-                input_segment_groups,
-                output_segment_groups,
-            )
-        })
+        .map(|line| parse_line(line).unwrap())
         .collect()
 }
 
-fn infer_obvious(line: &Line) -> ([Segments; 4], Vec<Segments>) {
-    let mut one = Segments::NONE;
-    let mut four = Segments::NONE;
-    let mut seven = Segments::NONE;
-    let mut eight = Segments::NONE;
-
-    let mut items = line.1.to_vec();
+/// A display font: the segments each glyph lights up, with segment `i` as bit `i`.
+#[derive(Debug, Clone)]
+pub struct Font {
+    segments: usize,
+    glyphs: Vec<(char, u32)>,
+}
 
-    items.retain_mut(|&mut i| {
-        let bits = i.count_bits();
+impl Font {
+    pub fn new(segments: usize, glyphs: Vec<(char, u32)>) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            (1..=32).contains(&segments),
+            "fonts must have between 1 and 32 segments"
+        );
+
+        let font = Font { segments, glyphs };
+        for (i, &(glyph, pattern)) in font.glyphs.iter().enumerate() {
+            anyhow::ensure!(
+                pattern & !font.all_segments() == 0,
+                "glyph {:?} uses segments outside the font",
+                glyph
+            );
+            anyhow::ensure!(
+                font.glyphs[..i].iter().all(|&(_, other)| other != pattern),
+                "glyph {:?} looks like an earlier glyph",
+                glyph
+            );
+        }
 
-        let inferred_digit = match bits {
-            2 => Some(&mut one),
-            4 => Some(&mut four),
-            3 => Some(&mut seven),
-            7 => Some(&mut eight),
-            _ => None,
-        };
+        Ok(font)
+    }
 
-        if let Some(inferred_digit) = inferred_digit {
-            *inferred_digit = i;
-            false
-        } else {
-            true
+    /// The ten digits of a standard seven-segment display.
+    pub fn digits() -> Self {
+        let digits = [
+            Segments::ZERO,
+            Segments::ONE,
+            Segments::TWO,
+            Segments::THREE,
+            Segments::FOUR,
+            Segments::FIVE,
+            Segments::SIX,
+            Segments::SEVEN,
+            Segments::EIGHT,
+            Segments::NINE,
+        ];
+
+        Font {
+            segments: 7,
+            glyphs: ('0'..='9')
+                .zip(digits)
+                .map(|(glyph, segments)| (glyph, segments.bits() as u32))
+                .collect(),
         }
-    });
+    }
+
+    /// The digits followed by A, b, C, d, E and F.
+    pub fn hex() -> Self {
+        let letters = [
+            Segments::EIGHT - Segments::G,
+            Segments::EIGHT - Segments::A - Segments::C,
+            Segments::A | Segments::B | Segments::E | Segments::G,
+            Segments::EIGHT - Segments::A - Segments::B,
+            Segments::EIGHT - Segments::C - Segments::F,
+            Segments::A | Segments::B | Segments::D | Segments::E,
+        ];
+
+        let mut font = Font::digits();
+        font.glyphs.extend(
+            ('A'..='F')
+                .zip(letters)
+                .map(|(glyph, segments)| (glyph, segments.bits() as u32)),
+        );
+        font
+    }
+
+    pub fn pattern(&self, glyph: char) -> Option<u32> {
+        self.glyphs
+            .iter()
+            .find(|&&(other, _)| other == glyph)
+            .map(|&(_, pattern)| pattern)
+    }
+
+    pub fn glyph(&self, pattern: u32) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|&&(_, other)| other == pattern)
+            .map(|&(glyph, _)| glyph)
+    }
+
+    fn all_segments(&self) -> u32 {
+        u32::MAX >> (32 - self.segments)
+    }
+
+    fn patterns_of_size(&self, size: u32) -> impl Iterator<Item = u32> + '_ {
+        self.glyphs
+            .iter()
+            .map(|&(_, pattern)| pattern)
+            .filter(move |pattern| pattern.count_ones() == size)
+    }
+}
 
-    assert_eq!(items.len(), 6);
+/// Which segment each wire is connected to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Wiring(pub Vec<usize>);
+
+impl Wiring {
+    /// The segments lit by a pattern of scrambled wires.
+    pub fn unscramble(&self, wires: u32) -> u32 {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(wire, _)| wires >> wire & 1 == 1)
+            .fold(0, |pattern, (_, &segment)| pattern | 1 << segment)
+    }
+
+    /// The wires that light up a pattern of segments.
+    pub fn scramble(&self, segments: u32) -> u32 {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|&(_, &segment)| segments >> segment & 1 == 1)
+            .fold(0, |wires, (wire, _)| wires | 1 << wire)
+    }
 
-    ([one, four, seven, eight], items)
+    pub fn random(segments: usize, rng: &mut Rng) -> Self {
+        let mut wiring = (0..segments).collect_vec();
+        rng.shuffle(&mut wiring);
+        Wiring(wiring)
+    }
 }
 
-fn infer_and_count_obvious_digits(line: &Line) -> usize {
-    let (known_digits, _) = infer_obvious(line);
-    line.2.iter().filter(|d| known_digits.contains(*d)).count()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// No wiring turns every observation into a glyph.
+    Inconsistent,
+    /// More than one wiring does, such as these two.
+    Ambiguous([Wiring; 2]),
 }
 
-fn solve_line(line: &Line) -> u32 {
-    let ([one, four, seven, eight], mut rest) = infer_obvious(line);
+impl Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::Inconsistent => write!(f, "no wiring is consistent with the observations"),
+            SolveError::Ambiguous(_) => write!(
+                f,
+                "more than one wiring is consistent with the observations"
+            ),
+        }
+    }
+}
 
-    let mut three = Segments::NONE;
-    let mut six = Segments::NONE;
+impl std::error::Error for SolveError {}
 
-    rest.retain(|&i| {
-        let xor_one = (i ^ one).count_bits();
+/// A wire pattern and the glyphs it might still be.
+struct Constraint {
+    wires: u32,
+    glyphs: Vec<u32>,
+}
 
-        match xor_one {
-            3 => {
-                three = i;
-                false
-            }
-            6 => {
-                six = i;
-                false
+struct Search<'a> {
+    constraints: &'a [Constraint],
+    candidates: Vec<u32>,
+    assignment: Vec<Option<usize>>,
+    solutions: Vec<Wiring>,
+    limit: usize,
+}
+
+impl<'a> Search<'a> {
+    fn new(font: &Font, constraints: &'a [Constraint], limit: usize) -> Self {
+        let all = font.all_segments();
+
+        // A lit wire must go to a segment lit in one of its glyphs, and a dark wire to one that
+        // is dark in one of them
+        let mut candidates = vec![all; font.segments];
+        for constraint in constraints {
+            let (lit, dark) = constraint.glyphs.iter().fold((0, 0), |(lit, dark), glyph| {
+                (lit | glyph, dark | !glyph & all)
+            });
+
+            for (wire, candidates) in candidates.iter_mut().enumerate() {
+                *candidates &= if constraint.wires >> wire & 1 == 1 {
+                    lit
+                } else {
+                    dark
+                };
             }
-            _ => true,
         }
-    });
 
-    assert_ne!(three, Segments::NONE);
-    assert_ne!(six, Segments::NONE);
+        Search {
+            constraints,
+            candidates,
+            assignment: vec![None; font.segments],
+            solutions: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Whether every constraint can still be met, given the wires assigned so far.
+    fn consistent(&self) -> bool {
+        self.constraints.iter().all(|constraint| {
+            let (mut lit, mut dark) = (0, 0);
+
+            for (wire, segment) in self.assignment.iter().enumerate() {
+                match segment {
+                    Some(segment) if constraint.wires >> wire & 1 == 1 => lit |= 1 << segment,
+                    Some(segment) => dark |= 1 << segment,
+                    None => {}
+                }
+            }
+
+            constraint
+                .glyphs
+                .iter()
+                .any(|&glyph| glyph & lit == lit && glyph & dark == 0)
+        })
+    }
 
-    let mut nine = Segments::NONE;
+    fn run(&mut self, used: u32) {
+        // Branch on the most constrained wire first
+        let wire = (0..self.assignment.len())
+            .filter(|&wire| self.assignment[wire].is_none())
+            .min_by_key(|&wire| (self.candidates[wire] & !used).count_ones());
+
+        let wire = match wire {
+            Some(wire) => wire,
+            None => {
+                let wiring = self.assignment.iter().map(|s| s.unwrap()).collect();
+                self.solutions.push(Wiring(wiring));
+                return;
+            }
+        };
 
-    rest.retain(|&i| {
-        let xor_three = (i ^ three).count_bits();
+        let mut options = self.candidates[wire] & !used;
+        while options != 0 && self.solutions.len() < self.limit {
+            let segment = options.trailing_zeros() as usize;
+            options &= options - 1;
 
-        match xor_three {
-            1 => {
-                nine = i;
-                false
+            self.assignment[wire] = Some(segment);
+            if self.consistent() {
+                self.run(used | 1 << segment);
             }
-            _ => true,
         }
-    });
 
-    assert_ne!(nine, Segments::NONE);
+        self.assignment[wire] = None;
+    }
+}
 
-    let mut two = Segments::NONE;
-    let mut zero = Segments::NONE;
-    let mut five = Segments::NONE;
+fn search(font: &Font, constraints: &[Constraint], limit: usize) -> Vec<Wiring> {
+    let mut search = Search::new(font, constraints, limit);
+    search.run(0);
+    search.solutions
+}
 
-    rest.retain(|&i| {
-        let xor_six = (i ^ six).count_bits();
+/// The glyphs with as many segments as the pattern has wires. Patterns with wires outside the
+/// font can't be anything.
+fn obvious_glyphs(font: &Font, wires: u32) -> Vec<u32> {
+    if wires & !font.all_segments() != 0 {
+        return Vec::new();
+    }
 
-        match xor_six {
-            1 => {
-                five = i;
-                false
-            }
-            2 => {
-                zero = i;
-                false
+    font.patterns_of_size(wires.count_ones()).collect()
+}
+
+fn constraints(font: &Font, patterns: &[u32]) -> Vec<Constraint> {
+    patterns
+        .iter()
+        .map(|&wires| Constraint {
+            wires,
+            glyphs: obvious_glyphs(font, wires),
+        })
+        .collect()
+}
+
+fn glyph_set(font: &Font, glyphs: impl IntoIterator<Item = u32>) -> BTreeSet<char> {
+    glyphs
+        .into_iter()
+        .map(|glyph| font.glyph(glyph).unwrap())
+        .collect()
+}
+
+/// The glyphs each pattern could be, going by its number of lit wires alone. With the standard
+/// digits, that pins down 1, 4, 7 and 8.
+pub fn infer_obvious(font: &Font, patterns: &[u32]) -> Vec<BTreeSet<char>> {
+    patterns
+        .iter()
+        .map(|&wires| glyph_set(font, obvious_glyphs(font, wires)))
+        .collect()
+}
+
+/// The glyphs each output could be under some wiring that agrees with every observation and
+/// output. Starts from the obvious glyphs and keeps a glyph only if a wiring supports it, so any
+/// number of observations works. An inconsistent line gives empty sets.
+pub fn possible_glyphs(font: &Font, observations: &[u32], outputs: &[u32]) -> Vec<BTreeSet<char>> {
+    let mut constraints = constraints(font, &[observations, outputs].concat());
+    let mut possible = vec![BTreeSet::new(); outputs.len()];
+
+    for i in 0..outputs.len() {
+        let index = observations.len() + i;
+
+        for glyph in constraints[index].glyphs.clone() {
+            if possible[i].contains(&font.glyph(glyph).unwrap()) {
+                continue;
             }
-            3 => {
-                two = i;
-                false
+
+            let glyphs = std::mem::replace(&mut constraints[index].glyphs, vec![glyph]);
+            let witness = search(font, &constraints, 1).pop();
+            constraints[index].glyphs = glyphs;
+
+            // The wiring supports a glyph for every output, not just this one
+            if let Some(wiring) = witness {
+                for (possible, &wires) in possible.iter_mut().zip(outputs) {
+                    possible.extend(glyph_set(font, [wiring.unscramble(wires)]));
+                }
             }
-            _ => true,
         }
-    });
+    }
 
-    assert_ne!(two, Segments::NONE);
-    assert_ne!(zero, Segments::NONE);
-    assert_ne!(five, Segments::NONE);
+    possible
+}
+
+/// Up to `limit` wirings under which each observed wire pattern is a glyph of the font. A font
+/// with n segments and few observations can have up to n! answers, so keep the limit small
+/// unless the observations pin the wiring down.
+pub fn solve(font: &Font, observations: &[u32], limit: usize) -> Vec<Wiring> {
+    search(font, &constraints(font, observations), limit)
+}
 
-    assert!(rest.is_empty());
+/// The only wiring that fits the observations. Stops searching at the second one.
+pub fn solve_unique(font: &Font, observations: &[u32]) -> Result<Wiring, SolveError> {
+    let mut wirings = solve(font, observations, 2);
 
-    assert_eq!(
-        [zero, one, two, three, four, five, six, seven, eight, nine]
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .len(),
-        10
+    match (wirings.pop(), wirings.pop()) {
+        (None, _) => Err(SolveError::Inconsistent),
+        (Some(wiring), None) => Ok(wiring),
+        (Some(second), Some(first)) => Err(SolveError::Ambiguous([first, second])),
+    }
+}
+
+fn count_obvious_outputs(line: &Line, font: &Font) -> usize {
+    infer_obvious(font, &line.2)
+        .iter()
+        .filter(|glyphs| glyphs.len() == 1)
+        .count()
+}
+
+fn solve_line(line: &Line, font: &Font) -> anyhow::Result<u32> {
+    possible_glyphs(font, &line.1, &line.2)
+        .iter()
+        .try_fold(0, |code, glyphs| {
+            let digit = match glyphs.iter().exactly_one() {
+                Ok(glyph) => glyph.to_digit(10),
+                Err(_) => None,
+            };
+            let digit = digit
+                .with_context(|| format!("output of {:?} could be any of {:?}", line.0, glyphs))?;
+
+            Ok(code * 10 + digit)
+        })
+}
+
+/// Writes wires as letters from `a`, in random order.
+fn wires_to_group(wires: u32, rng: &mut Rng) -> String {
+    let mut letters = (0..u32::BITS)
+        .filter(|&wire| wires >> wire & 1 == 1)
+        .map(|wire| (b'a' + wire as u8) as char)
+        .collect_vec();
+    rng.shuffle(&mut letters);
+    letters.into_iter().collect()
+}
+
+/// A line of input as captured from a display wired with `wiring`: every glyph of the font once
+/// in random order, then the glyphs of `text`.
+pub fn encode_line(
+    font: &Font,
+    wiring: &Wiring,
+    text: &str,
+    rng: &mut Rng,
+) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        font.segments <= 26,
+        "can't write more than 26 wires as letters"
+    );
+    anyhow::ensure!(
+        wiring.0.len() == font.segments,
+        "wiring has {} wires but the font has {} segments",
+        wiring.0.len(),
+        font.segments
     );
 
-    let decoded_digits: HashMap<Segments, char> = [
-        (zero, '0'),
-        (one, '1'),
-        (two, '2'),
-        (three, '3'),
-        (four, '4'),
-        (five, '5'),
-        (six, '6'),
-        (seven, '7'),
-        (eight, '8'),
-        (nine, '9'),
-    ]
-    .into_iter()
-    .collect();
-
-    let code = line
-        .2
+    let mut glyphs = font
+        .glyphs
+        .iter()
+        .map(|&(_, pattern)| pattern)
+        .collect_vec();
+    rng.shuffle(&mut glyphs);
+
+    let observations = glyphs
         .into_iter()
-        .map(|i| decoded_digits.get(&i).unwrap())
-        .collect::<String>()
-        .parse::<u32>()
-        .unwrap();
+        .map(|pattern| wires_to_group(wiring.scramble(pattern), rng))
+        .join(" ");
 
-    code
+    let outputs = text
+        .chars()
+        .map(|glyph| {
+            let pattern = font
+                .pattern(glyph)
+                .with_context(|| format!("the font has no glyph {:?}", glyph))?;
+            Ok(wires_to_group(wiring.scramble(pattern), rng))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .join(" ");
+
+    Ok(format!("{} | {}", observations, outputs))
 }
 
 pub fn a() {
     let input = read_input();
+    let font = Font::digits();
     let result = input
         .iter()
-        .map(|line| infer_and_count_obvious_digits(line))
+        .map(|l| count_obvious_outputs(l, &font))
         .sum::<usize>();
     println!("Day8a: {}", result);
 }
 
 pub fn b() {
     let input = read_input();
-    let result = input.into_iter().map(|l| solve_line(&l)).sum::<u32>();
+    let font = Font::digits();
+    let result = input
+        .iter()
+        .map(|l| solve_line(l, &font).unwrap())
+        .sum::<u32>();
     println!("Day8b: {}", result);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pattern(wires: &str) -> u32 {
+        group_to_segments(wires).unwrap()
+    }
+
+    // Scrambles every glyph of the font by sending segment `i` to wire `wires[i]`
+    fn scramble(font: &Font, wires: &[usize]) -> Vec<u32> {
+        font.glyphs
+            .iter()
+            .map(|&(_, glyph)| {
+                (0..font.segments)
+                    .filter(|&segment| glyph >> segment & 1 == 1)
+                    .fold(0, |pattern, segment| pattern | 1 << wires[segment])
+            })
+            .collect()
+    }
+
+    fn inverse(wires: &[usize]) -> Wiring {
+        let mut wiring = vec![0; wires.len()];
+        for (segment, &wire) in wires.iter().enumerate() {
+            wiring[wire] = segment;
+        }
+        Wiring(wiring)
+    }
+
+    #[test]
+    fn example_line() {
+        let observations = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+            .split(' ')
+            .map(pattern)
+            .collect_vec();
+
+        let font = Font::digits();
+        let wiring = solve_unique(&font, &observations).unwrap();
+
+        let decoded: String = ["cdfeb", "fcadb", "cdfeb", "cdbaf"]
+            .into_iter()
+            .map(|output| font.glyph(wiring.unscramble(pattern(output))).unwrap())
+            .collect();
+        assert_eq!(decoded, "5353");
+    }
+
+    #[test]
+    fn hex_font() {
+        let font = Font::hex();
+        let wires = [3, 6, 0, 5, 1, 4, 2];
+
+        let wiring = solve_unique(&font, &scramble(&font, &wires)).unwrap();
+        assert_eq!(wiring, inverse(&wires));
+    }
+
+    #[test]
+    fn custom_font() {
+        // A 2x2 grid of segments, where a glyph is told apart by its shape
+        let font = Font::new(
+            4,
+            vec![('.', 0b0000), ('-', 0b0011), ('/', 0b0110), ('L', 0b1011)],
+        )
+        .unwrap();
+        let wires = [2, 0, 3, 1];
+
+        assert_eq!(
+            solve(&font, &scramble(&font, &wires), usize::MAX),
+            vec![inverse(&wires)]
+        );
+        assert!(Font::new(4, vec![('a', 0b1), ('b', 0b1)]).is_err());
+        assert!(Font::new(4, vec![('a', 0b10000)]).is_err());
+        assert!(Font::new(0, vec![('a', 0)]).is_err());
+        assert!(Font::new(33, vec![('a', 1)]).is_err());
+        assert!(Font::new(32, vec![('a', 1 << 31)]).is_ok());
+    }
+
+    #[test]
+    fn ambiguous_and_inconsistent_observations() {
+        let font = Font::digits();
+
+        // "1" only says which two wires go to C and F
+        assert_eq!(solve(&font, &[pattern("ab")], usize::MAX).len(), 2 * 120);
+        assert_eq!(solve(&font, &[pattern("ab")], 10).len(), 10);
+        match solve_unique(&font, &[pattern("ab")]) {
+            Err(SolveError::Ambiguous(wirings)) => assert_ne!(wirings[0], wirings[1]),
+            other => panic!("expected ambiguity, got {:?}", other),
+        }
+
+        // No digit lights up a single segment
+        assert_eq!(
+            solve_unique(&font, &[pattern("a")]),
+            Err(SolveError::Inconsistent)
+        );
+        // Two different patterns can't both be "1"
+        assert_eq!(
+            solve_unique(&font, &[pattern("ab"), pattern("bc")]),
+            Err(SolveError::Inconsistent)
+        );
+    }
+
+    #[test]
+    fn ambiguity_stops_early_on_big_fonts() {
+        // 2 * 12! wirings fit, far too many to list
+        let font = Font::new(14, vec![('1', 0b11), ('7', 0b111)]).unwrap();
+
+        assert!(matches!(
+            solve_unique(&font, &[pattern("ab")]),
+            Err(SolveError::Ambiguous(_))
+        ));
+    }
+
+    #[test]
+    fn solver_matches_puzzle() {
+        let font = Font::digits();
+        let total: u32 = read_input()
+            .iter()
+            .map(|line| solve_line(line, &font).unwrap())
+            .sum();
+        assert_eq!(total, 1011823);
+    }
+
+    fn digits(glyphs: &str) -> BTreeSet<char> {
+        glyphs.chars().collect()
+    }
+
+    #[test]
+    fn obvious_digits() {
+        let line = parse_line(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | ab dab eafb cdfeb",
+        )
+        .unwrap();
+        let font = Font::digits();
+
+        assert_eq!(
+            infer_obvious(&font, &line.2),
+            vec![digits("1"), digits("7"), digits("4"), digits("235")]
+        );
+        assert_eq!(count_obvious_outputs(&line, &font), 3);
+    }
+
+    #[test]
+    fn partial_observations() {
+        let font = Font::digits();
+
+        // 1, 7 and 4 are enough to tell 2, 3 and 5 apart
+        let line = parse_line("ab dab eafb | cdfeb fcadb cdfeb cdbaf").unwrap();
+        assert_eq!(solve_line(&line, &font).unwrap(), 5353);
+
+        // Without them any two different five segment patterns could be any two of 2, 3 and 5
+        let line = parse_line("| cdfeb fcadb cdfeb").unwrap();
+        assert_eq!(
+            possible_glyphs(&font, &line.1, &line.2),
+            vec![digits("235"), digits("235"), digits("235")]
+        );
+        assert!(solve_line(&line, &font).is_err());
+
+        // Knowing 1 rules out 5 for the pattern containing both of its wires, and 3 for the one
+        // that doesn't
+        let line = parse_line("ab | fcadb cdfeb").unwrap();
+        assert_eq!(
+            possible_glyphs(&font, &line.1, &line.2),
+            vec![digits("3"), digits("25")]
+        );
+    }
+
+    #[test]
+    fn inconsistent_partial_observations() {
+        let font = Font::digits();
+
+        let line = parse_line("ab bc | ab").unwrap();
+        assert_eq!(possible_glyphs(&font, &line.1, &line.2), vec![digits("")]);
+        assert!(parse_line("abX | ab").is_err());
+
+        // Wires past g parse, but no digit can use them
+        let line = parse_line("abx | ab").unwrap();
+        assert_eq!(possible_glyphs(&font, &line.1, &line.2), vec![digits("")]);
+        assert!(parse_line("ab ab").is_err());
+    }
+
+    #[test]
+    fn encoded_lines_decode() {
+        let font = Font::digits();
+        let mut rng = Rng::new(2021);
+
+        for _ in 0..200 {
+            let wiring = Wiring::random(7, &mut rng);
+            let number = rng.below(10000) as u32;
+            let text = format!("{:04}", number);
+
+            let line = parse_line(&encode_line(&font, &wiring, &text, &mut rng).unwrap()).unwrap();
+            assert_eq!(line.1.len(), 10);
+            assert_eq!(solve_unique(&font, &line.1), Ok(wiring));
+            assert_eq!(solve_line(&line, &font).unwrap(), number);
+        }
+    }
+
+    #[test]
+    fn encoded_hex_lines_decode() {
+        let font = Font::hex();
+        let mut rng = Rng::new(16);
+
+        for _ in 0..50 {
+            let wiring = Wiring::random(7, &mut rng);
+            let text = (0..6)
+                .map(|_| font.glyphs[rng.below(16)].0)
+                .collect::<String>();
+
+            let line = parse_line(&encode_line(&font, &wiring, &text, &mut rng).unwrap()).unwrap();
+            let decoded = possible_glyphs(&font, &line.1, &line.2);
+            assert_eq!(
+                decoded,
+                text.chars().map(|c| BTreeSet::from([c])).collect_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn encoded_lines_with_more_segments_parse() {
+        // A row of 9 lamps, lit from the left
+        let font = Font::new(
+            9,
+            (1..=9)
+                .map(|n| ((b'0' + n) as char, (1 << n) - 1))
+                .collect(),
+        )
+        .unwrap();
+        let mut rng = Rng::new(9);
+        let wiring = Wiring::random(9, &mut rng);
+
+        let line = parse_line(&encode_line(&font, &wiring, "1959", &mut rng).unwrap()).unwrap();
+        assert_eq!(
+            line.2,
+            "1959"
+                .chars()
+                .map(|glyph| wiring.scramble(font.pattern(glyph).unwrap()))
+                .collect_vec()
+        );
+        assert_eq!(
+            possible_glyphs(&font, &line.1, &line.2),
+            "1959".chars().map(|c| BTreeSet::from([c])).collect_vec()
+        );
+    }
+
+    #[test]
+    fn encoding_is_seedable() {
+        let font = Font::digits();
+        let line = |seed| {
+            let mut rng = Rng::new(seed);
+            let wiring = Wiring::random(7, &mut rng);
+            encode_line(&font, &wiring, "8675", &mut rng).unwrap()
+        };
+
+        assert_eq!(line(7), line(7));
+        assert_ne!(line(7), line(8));
+        assert!(encode_line(
+            &font,
+            &Wiring::random(7, &mut Rng::new(0)),
+            "A",
+            &mut Rng::new(0)
+        )
+        .is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::HashSet;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+};
 
 use itertools::Itertools;
 
@@ -37,7 +40,7 @@ fn find_low_points(array: &Array2D<u8>) -> Vec<(usize, u8)> {
 
     let mut adjacent = Vec::new();
     for (i, x) in array.iter().enumerate() {
-        get_adjacent(&array, i, &mut adjacent);
+        get_adjacent(array, i, &mut adjacent);
 
         if adjacent.drain(..).all(|(_, adj)| adj > x) {
             low_points.push((i, x));
@@ -47,11 +50,299 @@ fn find_low_points(array: &Array2D<u8>) -> Vec<(usize, u8)> {
     low_points
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinimumMode {
+    /// Single cells lower than all of their neighbours.
+    Strict,
+    /// Flat regions lower than everything around them.
+    Plateau,
+    /// Cells no higher than any of their neighbours, grouped into flat regions. This includes
+    /// shelves that lead down to a lower region somewhere else.
+    NonStrict,
+}
+
+/// A connected region of cells at the same height.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowRegion {
+    pub cells: Vec<usize>,
+    pub level: u8,
+}
+
+impl LowRegion {
+    /// Risk is counted once per region, not once per cell.
+    pub fn risk(&self) -> usize {
+        self.level as usize + 1
+    }
+}
+
+/// Splits the included cells into connected regions of equal height, in order of their first
+/// cell.
+fn flat_regions(array: &Array2D<u8>, include: impl Fn(usize) -> bool) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; array.data.len()];
+    let mut regions = Vec::new();
+    let mut adjacent = Vec::new();
+
+    for start in 0..array.data.len() {
+        if seen[start] || !include(start) {
+            continue;
+        }
+
+        seen[start] = true;
+        let mut region = vec![start];
+        let mut next = 0;
+
+        while next < region.len() {
+            array.adjacent_cardinal(region[next], &mut adjacent);
+            for (j, height) in adjacent.drain(..) {
+                if !seen[j] && height == array.data[start] && include(j) {
+                    seen[j] = true;
+                    region.push(j);
+                }
+            }
+            next += 1;
+        }
+
+        region.sort_unstable();
+        regions.push(region);
+    }
+
+    regions
+}
+
+/// Whether each cell has a neighbour lower than itself.
+fn has_lower_neighbour(array: &Array2D<u8>) -> Vec<bool> {
+    let mut adjacent = Vec::new();
+
+    (0..array.data.len())
+        .map(|i| {
+            array.adjacent_cardinal(i, &mut adjacent);
+            adjacent.drain(..).any(|(_, height)| height < array.data[i])
+        })
+        .collect()
+}
+
+pub fn find_low_regions(array: &Array2D<u8>, mode: MinimumMode) -> Vec<LowRegion> {
+    let has_lower = has_lower_neighbour(array);
+
+    let regions = match mode {
+        MinimumMode::NonStrict => flat_regions(array, |i| !has_lower[i]),
+        MinimumMode::Strict | MinimumMode::Plateau => flat_regions(array, |_| true)
+            .into_iter()
+            .filter(|region| region.iter().all(|&i| !has_lower[i]))
+            .filter(|region| mode == MinimumMode::Plateau || region.len() == 1)
+            .collect(),
+    };
+
+    regions
+        .into_iter()
+        .map(|cells| LowRegion {
+            level: array.data[cells[0]],
+            cells,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Basin {
+    /// First cell of the flat minimum the basin drains to.
+    pub low_point: usize,
+    pub cells: Vec<usize>,
+    /// Height of the minimum.
+    pub floor: u8,
+    /// The level water can rise to before spilling into another basin: its lowest saddle, or the
+    /// wall height if it has none.
+    pub spill: u8,
+    /// Water held when filled up to the spill level.
+    pub volume: u32,
+}
+
+impl Basin {
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.spill - self.floor
+    }
+}
+
+/// The lowest pass between two adjacent basins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Saddle {
+    pub basins: (usize, usize),
+    pub cell: usize,
+    pub level: u8,
+}
+
+/// Two basins or earlier merges becoming one lake when the water reaches `level`. Node ids below
+/// the number of basins are basins, and merge `k` is node `basins.len() + k`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Merge {
+    pub level: u8,
+    pub saddle: usize,
+    pub children: [usize; 2],
+}
+
+/// The heightmap split into basins that drain to each minimum, where a flat minimum spanning
+/// several cells is a single basin. Cells at or above the wall height belong to no basin, and
+/// neither does anything beyond the edges.
+pub struct Watershed {
+    pub labels: Array2D<Option<usize>>,
+    pub basins: Vec<Basin>,
+    pub saddles: Vec<Saddle>,
+}
+
+impl Watershed {
+    pub fn new(heights: &Array2D<u8>, wall: u8) -> Self {
+        let minima = find_low_regions(heights, MinimumMode::Plateau)
+            .into_iter()
+            .filter(|region| region.level < wall)
+            .collect_vec();
+
+        // Flood from every cell of every minimum at once, always growing the lowest cell next
+        let mut labels = heights.map(|_| None);
+        let mut queue = BinaryHeap::new();
+        let mut order = 0;
+
+        for (basin, region) in minima.iter().enumerate() {
+            for &i in &region.cells {
+                queue.push(Reverse((region.level, order, i, basin)));
+                order += 1;
+            }
+        }
+
+        let mut adjacent = Vec::new();
+        while let Some(Reverse((_, _, i, basin))) = queue.pop() {
+            if labels.data[i].is_some() {
+                continue;
+            }
+            labels.data[i] = Some(basin);
+
+            heights.adjacent_cardinal(i, &mut adjacent);
+            for (j, height) in adjacent.drain(..) {
+                if height < wall && labels.data[j].is_none() {
+                    queue.push(Reverse((height, order, j, basin)));
+                    order += 1;
+                }
+            }
+        }
+
+        // The pass between two basins is the higher of two adjacent cells on either side
+        let mut passes = BTreeMap::new();
+        let mut neighbours = Vec::new();
+        for (i, label) in labels.iter().enumerate() {
+            let a = match label {
+                Some(a) => a,
+                None => continue,
+            };
+
+            labels.adjacent_cardinal(i, &mut neighbours);
+            for (j, label) in neighbours.drain(..) {
+                match label {
+                    Some(b) if b > a => {
+                        let pass = (heights.data[i], i).max((heights.data[j], j));
+                        let entry = passes.entry((a, b)).or_insert(pass);
+                        *entry = (*entry).min(pass);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let saddles = passes
+            .into_iter()
+            .map(|(basins, (level, cell))| Saddle {
+                basins,
+                cell,
+                level,
+            })
+            .sorted_by_key(|saddle| (saddle.level, saddle.cell))
+            .collect_vec();
+
+        let mut cells = vec![Vec::new(); minima.len()];
+        for (i, label) in labels.iter().enumerate() {
+            if let Some(basin) = label {
+                cells[basin].push(i);
+            }
+        }
+
+        let basins = minima
+            .into_iter()
+            .zip(cells)
+            .enumerate()
+            .map(|(id, (region, cells))| {
+                let spill = saddles
+                    .iter()
+                    .filter(|saddle| saddle.basins.0 == id || saddle.basins.1 == id)
+                    .map(|saddle| saddle.level)
+                    .min()
+                    .unwrap_or(wall);
+
+                let volume = cells
+                    .iter()
+                    .map(|&i| spill.saturating_sub(heights.data[i]) as u32)
+                    .sum();
+
+                Basin {
+                    low_point: region.cells[0],
+                    cells,
+                    floor: region.level,
+                    spill,
+                    volume,
+                }
+            })
+            .collect();
+
+        Watershed {
+            labels,
+            basins,
+            saddles,
+        }
+    }
+
+    /// How the basins join up as the water rises over each saddle in turn. Basins only ever
+    /// separated by walls end up in different trees.
+    pub fn merge_tree(&self) -> Vec<Merge> {
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        // Union-find over the basins, remembering the tree node each set currently is
+        let mut parent = (0..self.basins.len()).collect_vec();
+        let mut node = (0..self.basins.len()).collect_vec();
+        let mut merges = Vec::new();
+
+        for saddle in &self.saddles {
+            let a = find(&mut parent, saddle.basins.0);
+            let b = find(&mut parent, saddle.basins.1);
+
+            if a == b {
+                continue;
+            }
+
+            merges.push(Merge {
+                level: saddle.level,
+                saddle: saddle.cell,
+                children: [node[a], node[b]],
+            });
+
+            parent[b] = a;
+            node[a] = self.basins.len() + merges.len() - 1;
+        }
+
+        merges
+    }
+}
+
 pub fn a() {
     let array = read_input();
-    let risk_level = find_low_points(&array)
-        .into_iter()
-        .map(|(_, x)| (x + 1) as usize)
+    let risk_level = find_low_regions(&array, MinimumMode::Strict)
+        .iter()
+        .map(LowRegion::risk)
         .sum::<usize>();
 
     println!("Day9a: {}", risk_level);
@@ -59,29 +350,192 @@ pub fn a() {
 
 pub fn b() {
     let array = read_input();
-    let low_points = find_low_points(&array);
+    let watershed = Watershed::new(&array, 9);
+
+    let answer: usize = watershed
+        .basins
+        .iter()
+        .map(Basin::size)
+        .sorted()
+        .rev()
+        .take(3)
+        .product();
+    println!("Day9b: {}", answer);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "2199943210\n3987894921\n9856789892\n8767896789\n9899965678\n";
+
+    fn regions(heights: &str, mode: MinimumMode) -> Vec<(Vec<usize>, u8)> {
+        find_low_regions(&Array2D::from_string(heights), mode)
+            .into_iter()
+            .map(|region| (region.cells, region.level))
+            .collect()
+    }
+
+    #[test]
+    fn strict_regions_are_low_points() {
+        let array = Array2D::from_string(EXAMPLE);
+        let strict = find_low_regions(&array, MinimumMode::Strict);
+
+        assert_eq!(strict.iter().map(LowRegion::risk).sum::<usize>(), 15);
+        assert_eq!(
+            strict
+                .into_iter()
+                .map(|region| (region.cells[0], region.level))
+                .collect_vec(),
+            find_low_points(&array)
+        );
 
-    let mut basin_sizes = Vec::new();
+        let input = read_input();
+        assert_eq!(
+            find_low_regions(&input, MinimumMode::Strict).len(),
+            find_low_points(&input).len()
+        );
+    }
+
+    #[test]
+    fn flat_minima() {
+        let heights = "5115\n5225\n9999\n";
+        assert_eq!(regions(heights, MinimumMode::Strict), vec![]);
+        assert_eq!(
+            regions(heights, MinimumMode::Plateau),
+            vec![(vec![1, 2], 1)]
+        );
+        assert_eq!(
+            regions(heights, MinimumMode::NonStrict),
+            vec![(vec![1, 2], 1)]
+        );
+    }
 
-    for (i, _) in low_points {
-        let mut visited = HashSet::<usize>::new();
-        let mut queue = vec![i];
+    #[test]
+    fn shelves_are_only_non_strict() {
+        let heights = "2210\n";
+        assert_eq!(regions(heights, MinimumMode::Strict), vec![(vec![3], 0)]);
+        assert_eq!(regions(heights, MinimumMode::Plateau), vec![(vec![3], 0)]);
+        assert_eq!(
+            regions(heights, MinimumMode::NonStrict),
+            vec![(vec![0], 2), (vec![3], 0)]
+        );
 
-        while let Some(n) = queue.pop() {
-            if !visited.contains(&n) {
-                visited.insert(n);
-                let mut adjacent = Vec::new();
-                get_adjacent(&array, n, &mut adjacent);
-                adjacent.retain(|(_, x)| *x < 9);
-                queue.extend(adjacent.into_iter().map(|(i, _)| i));
+        // A flat map is one big minimum
+        assert_eq!(
+            regions("44\n44\n", MinimumMode::Plateau),
+            vec![(vec![0, 1, 2, 3], 4)]
+        );
+    }
+
+    #[test]
+    fn example_basins() {
+        let watershed = Watershed::new(&Array2D::from_string(EXAMPLE), 9);
+
+        let sizes = watershed.basins.iter().map(Basin::size).collect_vec();
+        assert_eq!(sizes, vec![3, 9, 14, 9]);
+        assert!(watershed.saddles.is_empty());
+        assert!(watershed.merge_tree().is_empty());
+        assert!(watershed.basins.iter().all(|basin| basin.spill == 9));
+        assert_eq!(watershed.basins[0].depth(), 8);
+    }
+
+    #[test]
+    fn saddle_between_two_basins() {
+        let heights = Array2D::from_string("31413\n");
+
+        let watershed = Watershed::new(&heights, 9);
+        assert_eq!(
+            watershed.saddles,
+            vec![Saddle {
+                basins: (0, 1),
+                cell: 2,
+                level: 4
+            }]
+        );
+        assert_eq!(
+            watershed.basins[0],
+            Basin {
+                low_point: 1,
+                cells: vec![0, 1, 2],
+                floor: 1,
+                spill: 4,
+                volume: 4,
             }
-        }
+        );
+        assert_eq!(watershed.basins[1].cells, vec![3, 4]);
+        assert_eq!(watershed.basins[1].depth(), 3);
+        assert_eq!(
+            watershed.merge_tree(),
+            vec![Merge {
+                level: 4,
+                saddle: 2,
+                children: [0, 1]
+            }]
+        );
 
-        basin_sizes.push(visited.len());
+        // With the pass as a wall the basins fill up to the wall instead
+        let watershed = Watershed::new(&heights, 4);
+        assert!(watershed.saddles.is_empty());
+        assert_eq!(watershed.labels.data[2], None);
+        assert_eq!(watershed.basins[1].volume, 4);
     }
 
-    let basin_sizes = basin_sizes.into_iter().sorted().rev().collect_vec();
+    #[test]
+    fn flat_minimum_is_one_basin() {
+        let watershed = Watershed::new(&Array2D::from_string("99999\n91119\n99999\n"), 9);
 
-    let answer: usize = basin_sizes.into_iter().take(3).product();
-    println!("Day9b: {}", answer);
+        assert_eq!(
+            watershed.basins,
+            vec![Basin {
+                low_point: 6,
+                cells: vec![6, 7, 8],
+                floor: 1,
+                spill: 9,
+                volume: 24,
+            }]
+        );
+        assert!(watershed.saddles.is_empty());
+    }
+
+    #[test]
+    fn basins_drain_to_plateau_minima() {
+        let input = read_input();
+        let watershed = Watershed::new(&input, 10);
+
+        assert_eq!(
+            watershed
+                .basins
+                .iter()
+                .map(|basin| (basin.low_point, basin.floor))
+                .collect_vec(),
+            find_low_regions(&input, MinimumMode::Plateau)
+                .into_iter()
+                .map(|region| (region.cells[0], region.level))
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn merge_tree_without_walls() {
+        let watershed = Watershed::new(&Array2D::from_string(EXAMPLE), 10);
+
+        assert_eq!(watershed.labels.iter().flatten().count(), 50);
+        assert!(watershed.saddles.iter().all(|saddle| saddle.level <= 9));
+
+        let merges = watershed.merge_tree();
+        assert_eq!(merges.len(), watershed.basins.len() - 1);
+        assert!(merges.windows(2).all(|w| w[0].level <= w[1].level));
+
+        // The last merge is the root, and every other node is a child exactly once
+        let children = merges
+            .iter()
+            .flat_map(|m| m.children)
+            .sorted()
+            .collect_vec();
+        assert_eq!(
+            children,
+            (0..watershed.basins.len() + merges.len() - 1).collect_vec()
+        );
+    }
 }
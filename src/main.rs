@@ -1,5 +1,10 @@
 #[allow(dead_code)]
 mod array2d;
+#[allow(dead_code)]
+mod bigint;
+#[allow(dead_code)]
+mod bitgrid;
+#[allow(dead_code)]
 mod util;
 
 #[allow(dead_code)]
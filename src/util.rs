@@ -1,3 +1,10 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    ops::{Add, Sub},
+    rc::Rc,
+};
+
 pub fn parse_lines(str: &str) -> Vec<i32> {
     str.lines()
         .map(|line| line.parse::<i32>().unwrap())
@@ -10,6 +17,7 @@ pub trait BoolIterUtil {
 }
 
 // What is the standard library equivalent of this?
+#[allow(clippy::wrong_self_convention)]
 pub trait AsOwned<T> {
     fn as_owned(self) -> T;
 }
@@ -38,3 +46,479 @@ where
         self.any(|b| b.as_owned())
     }
 }
+
+/// Ties go the way they do in std: the first minimum and the last maximum.
+pub trait IterUtil: Iterator + Sized {
+    fn count_where(self, mut predicate: impl FnMut(&Self::Item) -> bool) -> usize {
+        self.filter(|item| predicate(item)).count()
+    }
+
+    /// Returns the first minimum and the last maximum by the given key.
+    fn min_max_by_key<K: Ord + Clone>(
+        self,
+        mut key: impl FnMut(&Self::Item) -> K,
+    ) -> Option<(Self::Item, Self::Item)>
+    where
+        Self::Item: Clone,
+    {
+        let mut min: Option<(Self::Item, K)> = None;
+        let mut max: Option<(Self::Item, K)> = None;
+
+        for item in self {
+            let k = key(&item);
+
+            if min.as_ref().is_none_or(|(_, min_k)| k < *min_k) {
+                min = Some((item.clone(), k.clone()));
+            }
+
+            if max.as_ref().is_none_or(|(_, max_k)| k >= *max_k) {
+                max = Some((item, k));
+            }
+        }
+
+        min.zip(max).map(|((min, _), (max, _))| (min, max))
+    }
+
+    /// Counts each distinct item, sorted by item.
+    fn frequencies(self) -> Vec<(Self::Item, usize)>
+    where
+        Self::Item: Ord,
+    {
+        let mut items: Vec<_> = self.collect();
+        items.sort_unstable();
+
+        let mut frequencies: Vec<(Self::Item, usize)> = Vec::new();
+
+        for item in items {
+            match frequencies.last_mut() {
+                Some((last, count)) if *last == item => *count += 1,
+                _ => frequencies.push((item, 1)),
+            }
+        }
+
+        frequencies
+    }
+
+    /// Every unordered pair of distinct positions, in order of appearance.
+    fn pairwise_combinations(self) -> impl Iterator<Item = (Self::Item, Self::Item)>
+    where
+        Self::Item: Clone,
+    {
+        // Shared between the inner iterators, so only the yielded items are ever cloned
+        let items: Rc<[Self::Item]> = self.collect();
+
+        (0..items.len()).flat_map(move |i| {
+            let items = Rc::clone(&items);
+            (i + 1..items.len()).map(move |j| (items[i].clone(), items[j].clone()))
+        })
+    }
+
+    /// Sums of every window of `n` consecutive items, updated in constant time per item.
+    fn rolling_sum(self, n: usize) -> RollingSum<Self>
+    where
+        Self::Item: Copy + Default + Add<Output = Self::Item> + Sub<Output = Self::Item>,
+    {
+        assert!(n > 0, "window size must be positive");
+
+        RollingSum {
+            inner: self,
+            window: VecDeque::with_capacity(n),
+            sum: Default::default(),
+            n,
+        }
+    }
+
+    /// Index of the first minimum.
+    fn argmin(self) -> Option<usize>
+    where
+        Self::Item: Ord,
+    {
+        self.enumerate()
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Index of the last maximum.
+    fn argmax(self) -> Option<usize>
+    where
+        Self::Item: Ord,
+    {
+        self.enumerate()
+            .max_by(|(_, a), (_, b)| a.cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Index and value of the first item that has already been seen.
+    fn first_repeat(self) -> Option<(usize, Self::Item)>
+    where
+        Self::Item: Clone + Eq + Hash,
+    {
+        let mut seen = HashSet::new();
+
+        self.enumerate()
+            .find(|(_, item)| !seen.insert(item.clone()))
+    }
+}
+
+impl<I: Iterator> IterUtil for I {}
+
+pub struct RollingSum<I: Iterator> {
+    inner: I,
+    window: VecDeque<I::Item>,
+    sum: I::Item,
+    n: usize,
+}
+
+impl<I> Iterator for RollingSum<I>
+where
+    I: Iterator,
+    I::Item: Copy + Add<Output = I::Item> + Sub<Output = I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let item = self.inner.next()?;
+            self.window.push_back(item);
+            self.sum = self.sum + item;
+
+            if self.window.len() > self.n {
+                self.sum = self.sum - self.window.pop_front().unwrap();
+            }
+
+            if self.window.len() == self.n {
+                return Some(self.sum);
+            }
+        }
+    }
+}
+
+/// A cycle in the sequence of states produced by repeatedly applying a step function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    /// Index of the first state that is part of the cycle. The initial state has index 0.
+    pub start: usize,
+    /// Number of steps after which the states repeat.
+    pub period: usize,
+}
+
+impl Cycle {
+    /// Maps any step count (e.g. 10^12) to the earliest step that produces the same state.
+    pub fn equivalent_step(&self, n: u64) -> usize {
+        let start = self.start as u64;
+
+        if n < start {
+            n as usize
+        } else {
+            self.start + ((n - start) % self.period as u64) as usize
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Iteration<T> {
+    /// `done` returned true for the state reached after `steps` steps.
+    Finished { steps: usize, state: T },
+    /// The states started repeating before `done` returned true.
+    /// `states` contains every distinct state, starting from the initial one.
+    Cycle { cycle: Cycle, states: Vec<T> },
+}
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Applies `step` until `done` returns true for the new state, or until a state repeats.
+/// Every state is kept in memory, so each one is computed only once.
+pub fn iterate_until<T, F, P>(initial: T, mut step: F, mut done: P) -> Iteration<T>
+where
+    T: Eq + Hash,
+    F: FnMut(&T) -> T,
+    P: FnMut(&T) -> bool,
+{
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    seen.insert(hash_of(&initial), vec![0]);
+    let mut states = vec![initial];
+
+    loop {
+        let next = step(states.last().unwrap());
+        let steps = states.len();
+
+        if done(&next) {
+            return Iteration::Finished { steps, state: next };
+        }
+
+        let indices = seen.entry(hash_of(&next)).or_default();
+
+        if let Some(&start) = indices.iter().find(|&&i| states[i] == next) {
+            let cycle = Cycle {
+                start,
+                period: steps - start,
+            };
+
+            return Iteration::Cycle { cycle, states };
+        }
+
+        indices.push(steps);
+        states.push(next);
+    }
+}
+
+/// Finds the cycle with Brent's algorithm, keeping only two states in memory at a time.
+/// Never returns if the sequence doesn't cycle.
+pub fn find_cycle<T: Clone + PartialEq>(initial: T, mut step: impl FnMut(&T) -> T) -> Cycle {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+
+        hare = step(&hare);
+        period += 1;
+    }
+
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, period }
+}
+
+/// Returns the state after `n` steps. States are stepped through one by one, but once one
+/// repeats the rest of the way is skipped over in whole cycles.
+pub fn nth_state<T: Eq + Hash>(initial: T, step: impl FnMut(&T) -> T, n: u64) -> T {
+    if n == 0 {
+        return initial;
+    }
+
+    let mut steps = 0;
+    let done = |_: &T| {
+        steps += 1;
+        steps == n
+    };
+
+    match iterate_until(initial, step, done) {
+        Iteration::Finished { state, .. } => state,
+        Iteration::Cycle { cycle, mut states } => states.swap_remove(cycle.equivalent_step(n)),
+    }
+}
+
+/// Applies `step` until it no longer changes the state.
+pub fn fixpoint<T: PartialEq>(mut state: T, mut step: impl FnMut(&T) -> T) -> T {
+    loop {
+        let next = step(&state);
+
+        if next == state {
+            return next;
+        }
+
+        state = next;
+    }
+}
+
+/// A small seedable pseudo-random number generator (SplitMix64) for generating inputs.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`. The bias is negligible for any `n` much smaller than 2^64.
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 3, 10, 101, 2, 5, 26, 167, 95, 101, ...
+    fn step(x: &u32) -> u32 {
+        (x * x + 1) % 255
+    }
+
+    const CYCLE: Cycle = Cycle {
+        start: 2,
+        period: 6,
+    };
+
+    #[test]
+    fn brent_finds_cycle() {
+        assert_eq!(find_cycle(3, step), CYCLE);
+    }
+
+    #[test]
+    fn iterate_until_finds_cycle() {
+        match iterate_until(3, step, |_| false) {
+            Iteration::Cycle { cycle, states } => {
+                assert_eq!(cycle, CYCLE);
+                assert_eq!(states, vec![3, 10, 101, 2, 5, 26, 167, 95]);
+            }
+            Iteration::Finished { .. } => panic!("expected a cycle"),
+        }
+    }
+
+    #[test]
+    fn iterate_until_finishes() {
+        match iterate_until(3, step, |&x| x == 26) {
+            Iteration::Finished { steps, state } => {
+                assert_eq!(steps, 5);
+                assert_eq!(state, 26);
+            }
+            Iteration::Cycle { .. } => panic!("expected to finish"),
+        }
+    }
+
+    #[test]
+    fn extrapolate_huge_step_count() {
+        assert_eq!(CYCLE.equivalent_step(1), 1);
+        assert_eq!(CYCLE.equivalent_step(8), 2);
+        assert_eq!(nth_state(3, step, 1_000_000_000_000), 5);
+        assert_eq!(nth_state(3, step, 0), 3);
+        assert_eq!(nth_state(3, step, 2), 101);
+    }
+
+    #[test]
+    fn count_where_and_first_repeat() {
+        assert_eq!((1..=10).count_where(|x| x % 3 == 0), 3);
+        assert_eq!([1, 5, 2, 5, 1].into_iter().first_repeat(), Some((3, 5)));
+        assert_eq!([1, 2, 3].into_iter().first_repeat(), None);
+    }
+
+    #[test]
+    fn min_max_by_key_breaks_ties() {
+        let words = ["bb", "a", "cc", "d"];
+        assert_eq!(
+            words.into_iter().min_max_by_key(|w| w.len()),
+            Some(("a", "cc"))
+        );
+        assert_eq!(std::iter::empty::<u8>().min_max_by_key(|&x| x), None);
+    }
+
+    #[test]
+    fn frequencies_are_sorted() {
+        assert_eq!(
+            "NNCB".chars().frequencies(),
+            vec![('B', 1), ('C', 1), ('N', 2)]
+        );
+    }
+
+    #[test]
+    fn pairwise_combinations_of_three() {
+        assert_eq!(
+            [1, 2, 3]
+                .into_iter()
+                .pairwise_combinations()
+                .collect::<Vec<_>>(),
+            vec![(1, 2), (1, 3), (2, 3)]
+        );
+        assert_eq!(std::iter::once(1).pairwise_combinations().count(), 0);
+    }
+
+    #[test]
+    fn pairwise_combinations_clone_only_yielded_items() {
+        use std::cell::Cell;
+
+        thread_local!(static CLONES: Cell<usize> = const { Cell::new(0) });
+
+        struct Counted;
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.with(|clones| clones.set(clones.get() + 1));
+                Counted
+            }
+        }
+
+        let pairs = (0..20).map(|_| Counted).pairwise_combinations().count();
+        assert_eq!(pairs, 190);
+        assert_eq!(CLONES.with(Cell::get), 2 * 190);
+    }
+
+    #[test]
+    fn rolling_sum_day1_example() {
+        let depths = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+        assert_eq!(
+            depths.into_iter().rolling_sum(3).collect::<Vec<i32>>(),
+            vec![607, 618, 618, 617, 647, 716, 769, 792]
+        );
+        assert_eq!(depths.into_iter().rolling_sum(11).next(), None);
+    }
+
+    #[test]
+    fn argmin_and_argmax() {
+        let values = [3, 1, 4, 1, 5, 9, 2, 9];
+        assert_eq!(values.iter().argmin(), Some(1));
+        assert_eq!(values.iter().argmax(), Some(7));
+        assert_eq!(values.iter().argmax(), values.iter().rposition(|&v| v == 9));
+        assert_eq!(std::iter::empty::<u8>().argmin(), None);
+    }
+
+    #[test]
+    fn nth_state_without_cycle() {
+        assert_eq!(nth_state(0u64, |x| x + 1, 5), 5);
+        assert_eq!(nth_state(7u64, |x| x * 2, 3), 56);
+    }
+
+    #[test]
+    fn fixpoint_of_halving() {
+        assert_eq!(fixpoint(1000, |x| x / 2), 0);
+    }
+
+    #[test]
+    fn rng_is_seedable() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        assert_eq!(
+            (0..5).map(|_| a.next_u64()).collect::<Vec<_>>(),
+            (0..5).map(|_| b.next_u64()).collect::<Vec<_>>()
+        );
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+
+        let mut items = (0..20).collect::<Vec<_>>();
+        a.shuffle(&mut items);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+
+        assert!((0..1000).all(|_| a.below(7) < 7));
+    }
+}